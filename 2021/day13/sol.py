@@ -1,8 +1,13 @@
+import sys
 from collections import defaultdict
+from pathlib import Path
 from typing import Dict
 
 import numpy as np
 
+sys.path.append(str(Path(__file__).resolve().parents[2] / "common"))
+import ocr  # noqa: E402
+
 
 def read_instructions(filename: str) -> Dict[complex, int]:
 
@@ -35,15 +40,13 @@ def fold(dotmap: Dict[complex, int], axis: complex) -> None:
         dotmap[add] += 1
 
 
-def visualize(dotmap: Dict[complex, int]) -> None:
+def visualize(dotmap: Dict[complex, int]) -> np.ndarray:
 
     canvas = np.full((39, 6), " ")  # Big enough.
     for coord in dotmap:
         canvas[int(coord.real), int(coord.imag)] = "█"
 
-    canvas = np.rot90(np.flipud(canvas), 3)
-    for line in canvas:
-        print("".join(line))
+    return np.rot90(np.flipud(canvas), 3)
 
 
 if __name__ == "__main__":
@@ -70,4 +73,10 @@ if __name__ == "__main__":
             checksum = False
             print(len(dotmap))  # Part 1.
 
-    visualize(dotmap)  # Part 2.
+    canvas = visualize(dotmap)
+    try:
+        print(ocr.recognise(canvas == "█"))  # Part 2.
+    except ocr.OcrError as error:
+        print(error)
+        for line in canvas:
+            print("".join(line))
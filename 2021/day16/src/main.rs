@@ -1,4 +1,6 @@
-#[derive(PartialEq)]
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 enum PacketType {
     Sum,
     Prod,
@@ -26,6 +28,59 @@ impl From<u64> for PacketType {
     }
 }
 
+impl From<PacketType> for u64 {
+    fn from(typ: PacketType) -> Self {
+        typ as u64
+    }
+}
+
+const OPERATORS: [(&str, PacketType); 7] = [
+    ("+", PacketType::Sum),
+    ("*", PacketType::Prod),
+    ("min", PacketType::Min),
+    ("max", PacketType::Max),
+    (">", PacketType::Gt),
+    ("<", PacketType::Lt),
+    ("=", PacketType::Eq),
+];
+
+impl PacketType {
+    fn symbol(&self) -> &'static str {
+        OPERATORS.iter().find(|(_, t)| t == self).unwrap().0
+    }
+}
+
+/// How an operator packet tells how many sub-packets it holds.
+#[derive(Clone, Copy)]
+enum LengthMode {
+    TotalBits,
+    Count,
+}
+
+/// Operator packets whose size doesn't fit the length field of the chosen mode.
+#[derive(Debug, PartialEq)]
+enum EncodeError {
+    TooManySubPackets(usize),
+    SubPacketsTooLong(usize),
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooManySubPackets(count) => {
+                write!(f, "{count} sub-packets don't fit the 11 bit count field")
+            }
+            Self::SubPacketsTooLong(bits) => {
+                write!(
+                    f,
+                    "{bits} bits of sub-packets don't fit the 15 bit length field"
+                )
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
 struct Packet {
     version: u64,
     typ: PacketType,
@@ -34,6 +89,57 @@ struct Packet {
 }
 
 impl Packet {
+    fn literal(version: u64, value: u64) -> Self {
+        Packet {
+            version,
+            typ: PacketType::Literal,
+            body: Some(value),
+            children: None,
+        }
+    }
+
+    fn operator(version: u64, typ: PacketType, children: Vec<Packet>) -> Self {
+        Packet {
+            version,
+            typ,
+            body: None,
+            children: Some(children),
+        }
+    }
+
+    /// Parses an S-expression like `(+ 1 (* 2 3))`, every packet gets version 0.
+    fn from_sexpr(expression: &str) -> Self {
+        let spaced = expression.replace('(', " ( ").replace(')', " ) ");
+        let tokens = spaced.split_whitespace().collect::<Vec<&str>>();
+        let mut pos = 0;
+        let packet = Self::parse_sexpr(&tokens, &mut pos);
+        assert_eq!(pos, tokens.len(), "Trailing tokens in {expression}");
+        packet
+    }
+
+    fn parse_sexpr(tokens: &[&str], pos: &mut usize) -> Self {
+        let token = tokens.get(*pos).expect("Unexpected end of expression");
+        *pos += 1;
+        if *token != "(" {
+            return Packet::literal(0, token.parse().unwrap());
+        }
+
+        let symbol = tokens.get(*pos).expect("Missing operator");
+        *pos += 1;
+        let typ = OPERATORS
+            .iter()
+            .find(|(s, _)| s == symbol)
+            .unwrap_or_else(|| panic!("Unknown operator {symbol}"))
+            .1;
+
+        let mut children = Vec::new();
+        while tokens.get(*pos).expect("Unbalanced parentheses") != &")" {
+            children.push(Self::parse_sexpr(tokens, pos));
+        }
+        *pos += 1;
+        Packet::operator(0, typ, children)
+    }
+
     fn sum_verstion(&self) -> u64 {
         if self.body.is_some() {
             return self.version;
@@ -70,142 +176,297 @@ impl Packet {
             PacketType::Literal => panic!("Should not reach"),
         }
     }
-}
 
-struct BITStreamSlice<T> {
-    bits: Vec<T>,
-}
+    fn encode(&self, writer: &mut BitWriter, mode: LengthMode) -> Result<(), EncodeError> {
+        writer.write(self.version, 3);
+        writer.write(self.typ.into(), 3);
 
-impl<T> BITStreamSlice<T> {
-    fn into_raw(self) -> Vec<T> {
-        self.bits
-    }
-}
+        if let Some(value) = self.body {
+            let groups = (u64::BITS - value.leading_zeros()).div_ceil(4).max(1);
+            for group in (0..groups).rev() {
+                writer.write((group > 0) as u64, 1);
+                writer.write(value >> (group * 4) & 0xf, 4);
+            }
+            return Ok(());
+        }
 
-impl BITStreamSlice<u64> {
-    fn into_u64(self) -> u64 {
-        let mut acc = 0;
-        for (n, i) in self.bits.iter().rev().enumerate() {
-            acc += i * 2_u64.pow(n as u32);
+        let children = self.children.as_ref().unwrap();
+        match mode {
+            LengthMode::TotalBits => {
+                let mut sub_packets = BitWriter::default();
+                for child in children {
+                    child.encode(&mut sub_packets, mode)?;
+                }
+                if sub_packets.len >= 1 << 15 {
+                    return Err(EncodeError::SubPacketsTooLong(sub_packets.len));
+                }
+                writer.write(0, 1);
+                writer.write(sub_packets.len as u64, 15);
+                writer.append(&sub_packets);
+            }
+            LengthMode::Count => {
+                if children.len() >= 1 << 11 {
+                    return Err(EncodeError::TooManySubPackets(children.len()));
+                }
+                writer.write(1, 1);
+                writer.write(children.len() as u64, 11);
+                for child in children {
+                    child.encode(writer, mode)?;
+                }
+            }
         }
-        acc
+        Ok(())
     }
 
-    fn into_usize(self) -> usize {
-        self.into_u64() as usize
+    fn to_hex(&self, mode: LengthMode) -> Result<String, EncodeError> {
+        let mut writer = BitWriter::default();
+        self.encode(&mut writer, mode)?;
+        Ok(writer.to_hex())
+    }
+
+    fn pretty(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        if let Some(value) = self.body {
+            return write!(f, "{value}");
+        }
+
+        let children = self.children.as_ref().unwrap();
+        write!(f, "({}", self.typ.symbol())?;
+        // Operators over plain literals stay on a single line.
+        if children.iter().all(|c| c.body.is_some()) {
+            for child in children {
+                write!(f, " {child}")?;
+            }
+        } else {
+            for child in children {
+                write!(f, "\n{}", "  ".repeat(depth + 1))?;
+                child.pretty(f, depth + 1)?;
+            }
+        }
+        write!(f, ")")
     }
 }
 
-impl From<Vec<u64>> for BITStreamSlice<u64> {
-    fn from(bits: Vec<u64>) -> Self {
-        BITStreamSlice { bits }
+impl fmt::Display for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.pretty(f, 0)
     }
 }
 
-#[derive(Default)]
-struct BITStream {
+/// Reads big-endian bit fields of up to 64 bits out of a byte slice.
+struct BitReader<'a> {
+    bytes: &'a [u8],
     consumed: usize,
-    bits: Vec<u64>,
 }
 
-impl From<&str> for BITStream {
-    fn from(stream: &str) -> Self {
-        let bits = stream
-            .chars()
-            .into_iter()
-            .map(|c| c.to_digit(10).unwrap() as u64)
-            .collect::<Vec<u64>>();
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        BitReader { bytes, consumed: 0 }
+    }
 
-        BITStream {
-            bits,
-            ..Default::default()
+    fn take(&mut self, n: usize) -> u64 {
+        let mut acc = 0;
+        for _ in 0..n {
+            let bit = self.bytes[self.consumed / 8] >> (7 - self.consumed % 8) & 1;
+            acc = acc << 1 | bit as u64;
+            self.consumed += 1;
         }
+        acc
+    }
+
+    /// Whether everything left over is padding.
+    fn only_padding_left(&self) -> bool {
+        let len = self.bytes.len() * 8;
+        (self.consumed..len).all(|pos| self.bytes[pos / 8] >> (7 - pos % 8) & 1 == 0)
     }
 }
 
-impl BITStream {
-    fn take(&mut self, n: usize) -> BITStreamSlice<u64> {
-        self.consumed += n;
-        let bits = Vec::from_iter(self.bits.drain(..n));
-        BITStreamSlice::from(bits)
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    len: usize,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u64, n: usize) {
+        for shift in (0..n).rev() {
+            if self.len.is_multiple_of(8) {
+                self.bytes.push(0);
+            }
+            let bit = (value >> shift & 1) as u8;
+            *self.bytes.last_mut().unwrap() |= bit << (7 - self.len % 8);
+            self.len += 1;
+        }
+    }
+
+    fn append(&mut self, other: &BitWriter) {
+        let mut reader = BitReader::new(&other.bytes);
+        for _ in 0..other.len {
+            self.write(reader.take(1), 1);
+        }
     }
 
-    fn checksum(&self) -> u64 {
-        self.bits.iter().sum()
+    fn to_hex(&self) -> String {
+        self.bytes
+            .iter()
+            .map(|byte| format!("{byte:02X}"))
+            .collect()
     }
 }
 
-fn decode_message(message: &str, buffer: &mut String) {
+fn decode_message(message: &str) -> Vec<u8> {
     message
         .chars()
         .map(|c| c.to_digit(16).unwrap() as u8)
         .collect::<Vec<u8>>()
         .chunks(2)
-        .for_each(|bytes| {
-            let byte = bytes[0] << 4 | bytes[1];
-            buffer.push_str(&format!("{:08b}", byte));
-        });
+        .map(|bytes| bytes[0] << 4 | bytes.get(1).unwrap_or(&0))
+        .collect()
 }
 
-fn parse_packets(stream: &mut BITStream) -> Packet {
-    let version = stream.take(3).into_u64();
-    let typ = PacketType::from(stream.take(3).into_u64());
+fn parse_packets(stream: &mut BitReader) -> Packet {
+    let version = stream.take(3);
+    let typ = PacketType::from(stream.take(3));
 
     if typ == PacketType::Literal {
-        let mut lit: Vec<u64> = Vec::new();
+        let mut body = 0;
         loop {
-            let group = stream.take(5).into_raw();
-            lit.extend(group[1..].iter());
-            if group[0] == 0 {
+            let group = stream.take(5);
+            body = body << 4 | group & 0xf;
+            if group >> 4 == 0 {
                 break;
             }
         }
 
-        let body = BITStreamSlice::from(lit).into_u64();
-        Packet {
-            version,
-            typ,
-            body: Some(body),
-            children: None,
-        }
+        Packet::literal(version, body)
     } else {
-        let len_type_id = stream.take(1).into_u64();
+        let len_type_id = stream.take(1);
         let mut children: Vec<Packet> = Vec::new();
         if len_type_id == 0 {
-            let to_read = stream.take(15).into_usize();
+            let to_read = stream.take(15) as usize;
             let before = stream.consumed;
-            loop {
-                let child = parse_packets(stream);
-                children.push(child);
-                if stream.consumed - before == to_read {
-                    break;
-                }
+            while stream.consumed - before < to_read {
+                children.push(parse_packets(stream));
             }
         } else {
-            let num_subpackets = stream.take(11).into_u64();
+            let num_subpackets = stream.take(11);
             for _ in 0..num_subpackets {
-                let child = parse_packets(stream);
-                children.push(child);
+                children.push(parse_packets(stream));
             }
         }
 
-        Packet {
-            version,
-            typ,
-            body: None,
-            children: Some(children),
-        }
+        Packet::operator(version, typ, children)
     }
 }
 
 fn main() {
-    let mut buffer = String::new();
-    decode_message(include_str!("../d16.txt").trim(), &mut buffer);
+    let bytes = decode_message(include_str!("../d16.txt").trim());
 
-    let mut stream = BITStream::from(buffer.as_str());
+    let mut stream = BitReader::new(&bytes);
     let packet = parse_packets(&mut stream);
 
-    assert_eq!(stream.checksum(), 0);
+    assert!(stream.only_padding_left());
     println!("{}", packet.sum_verstion());
     println!("{}", packet.eval_expression());
+
+    // `-- --encode "(+ 1 (* 2 3))"` turns an expression into a transmission instead.
+    let args = std::env::args().collect::<Vec<String>>();
+    if let Some(idx) = args.iter().position(|arg| arg == "--encode") {
+        let expression = Packet::from_sexpr(&args[idx + 1]);
+        println!("\n{expression}");
+        for mode in [LengthMode::TotalBits, LengthMode::Count] {
+            match expression.to_hex(mode) {
+                Ok(hex) => println!("{hex}"),
+                Err(e) => println!("{e}"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODES: [LengthMode; 2] = [LengthMode::TotalBits, LengthMode::Count];
+
+    /// splitmix64, plenty to grow random packet trees.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self, bound: u64) -> u64 {
+            self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+            let mut z = self.0;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            (z ^ (z >> 31)) % bound
+        }
+    }
+
+    fn random_packet(rng: &mut Rng, depth: usize) -> Packet {
+        let version = rng.next(8);
+        if depth == 0 || rng.next(3) == 0 {
+            let value = rng.next(u64::MAX) >> rng.next(64);
+            return Packet::literal(version, value);
+        }
+
+        let typ = OPERATORS[rng.next(OPERATORS.len() as u64) as usize].1;
+        let n_children = match typ {
+            PacketType::Gt | PacketType::Lt | PacketType::Eq => 2,
+            _ => 1 + rng.next(4) as usize,
+        };
+        let children = (0..n_children)
+            .map(|_| random_packet(rng, depth - 1))
+            .collect();
+        Packet::operator(version, typ, children)
+    }
+
+    fn decode(hex: &str) -> Packet {
+        let bytes = decode_message(hex);
+        let mut stream = BitReader::new(&bytes);
+        let packet = parse_packets(&mut stream);
+        assert!(stream.only_padding_left());
+        packet
+    }
+
+    #[test]
+    fn round_trips_random_trees() {
+        let mut rng = Rng(16);
+        for _ in 0..1000 {
+            let packet = random_packet(&mut rng, 4);
+            for mode in MODES {
+                assert_eq!(decode(&packet.to_hex(mode).unwrap()), packet);
+            }
+        }
+    }
+
+    #[test]
+    fn encodes_expressions() {
+        let expression = Packet::from_sexpr("(+ 1 (* 2 3) (min 7 (max 4 5)))");
+        for mode in MODES {
+            let decoded = decode(&expression.to_hex(mode).unwrap());
+            assert_eq!(decoded.eval_expression(), 12);
+            assert_eq!(
+                decoded.to_string(),
+                "(+\n  1\n  (* 2 3)\n  (min\n    7\n    (max 4 5)))"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_oversized_operators() {
+        let many = (0..2048).map(|v| Packet::literal(0, v)).collect();
+        let many = Packet::operator(0, PacketType::Sum, many);
+        assert_eq!(
+            many.to_hex(LengthMode::Count),
+            Err(EncodeError::TooManySubPackets(2048))
+        );
+
+        // 400 literals of 86 bits each, fine to count but too long to measure.
+        let long = (0..400).map(|_| Packet::literal(0, u64::MAX)).collect();
+        let long = Packet::operator(0, PacketType::Sum, long);
+        assert_eq!(
+            long.to_hex(LengthMode::TotalBits),
+            Err(EncodeError::SubPacketsTooLong(400 * 86))
+        );
+        assert!(long.to_hex(LengthMode::Count).is_ok());
+    }
 }
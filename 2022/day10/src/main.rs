@@ -1,23 +1,73 @@
-enum Instructions {
-    Noop,
-    Addx(i32),
+#[path = "../../../common/ocr.rs"]
+mod ocr;
+
+// The puzzle only uses `x`, the rest are free for other instruction sets.
+const REGISTERS: usize = 4;
+const X: usize = 0;
+
+/// Everything an instruction can change, and a device on the clock can see.
+#[derive(Debug, Clone)]
+struct CpuState {
+    clock: usize,
+    registers: [i32; REGISTERS],
+    // Mnemonic of the instruction currently in flight.
+    executing: &'static str,
+    retired: usize,
 }
 
-impl From<&str> for Instructions {
-    fn from(line: &str) -> Self {
-        let line = line.split_whitespace().collect::<Vec<&str>>().join("");
-        let (instruction, data) = line.split_at(4);
+struct Opcode {
+    mnemonic: &'static str,
+    operands: usize,
+    // Clock cycles it takes before the result lands in the registers.
+    cycles: usize,
+    execute: fn(&mut [i32; REGISTERS], &[i32]),
+}
 
-        match instruction {
-            "noop" => Self::Noop,
-            "addx" => Self::Addx(data.parse::<i32>().unwrap()),
-            _ => panic!(),
-        }
+const INSTRUCTION_SET: [Opcode; 2] = [
+    Opcode {
+        mnemonic: "noop",
+        operands: 0,
+        cycles: 1,
+        execute: |_, _| {},
+    },
+    Opcode {
+        mnemonic: "addx",
+        operands: 1,
+        cycles: 2,
+        execute: |registers, args| registers[X] += args[0],
+    },
+];
+
+struct Instruction {
+    opcode: &'static Opcode,
+    args: Vec<i32>,
+}
+
+impl Instruction {
+    fn decode(line: &str, instruction_set: &'static [Opcode]) -> Self {
+        let mut tokens = line.split_whitespace();
+        let mnemonic = tokens.next().unwrap();
+        let opcode = instruction_set
+            .iter()
+            .find(|op| op.mnemonic == mnemonic)
+            .unwrap_or_else(|| panic!("Unknown instruction {mnemonic}"));
+        let args = tokens
+            .map(|arg| arg.parse::<i32>().unwrap())
+            .collect::<Vec<i32>>();
+        assert_eq!(args.len(), opcode.operands, "Wrong operand count: {line}");
+
+        Instruction { opcode, args }
+    }
+}
+
+impl From<&str> for Instruction {
+    fn from(line: &str) -> Self {
+        Instruction::decode(line, &INSTRUCTION_SET)
     }
 }
 
 trait OnClockTick {
-    fn sample(&mut self, tick: usize, register: i32);
+    fn sample(&mut self, state: &CpuState);
 }
 
 struct CpuProbe {
@@ -43,9 +93,9 @@ impl CpuProbe {
 }
 
 impl OnClockTick for CpuProbe {
-    fn sample(&mut self, tick: usize, register: i32) {
-        if self.sampling_points.contains(&tick) {
-            self.register_state.push(register);
+    fn sample(&mut self, state: &CpuState) {
+        if self.sampling_points.contains(&state.clock) {
+            self.register_state.push(state.registers[X]);
         }
     }
 }
@@ -65,15 +115,24 @@ impl Crt {
     fn render_image(&self) {
         self.screen_buffer
             .chunks(40)
-            .into_iter()
             .for_each(|chunk| println!("{}", chunk.iter().collect::<String>()));
     }
+
+    fn read_letters(&self) -> Result<String, ocr::OcrError> {
+        let pixels = self
+            .screen_buffer
+            .chunks(40)
+            .map(|chunk| chunk.iter().map(|c| *c == '█').collect())
+            .collect::<Vec<Vec<bool>>>();
+        ocr::recognise(&pixels)
+    }
 }
 
 impl OnClockTick for Crt {
-    fn sample(&mut self, tick: usize, register: i32) {
-        let beam = (tick as i32 - 1) % 40;
-        if register - 1 <= beam && beam <= register + 1 {
+    fn sample(&mut self, state: &CpuState) {
+        let beam = (state.clock as i32 - 1) % 40;
+        let sprite = state.registers[X];
+        if sprite - 1 <= beam && beam <= sprite + 1 {
             self.screen_buffer.push('█');
         } else {
             self.screen_buffer.push(' ');
@@ -82,16 +141,22 @@ impl OnClockTick for Crt {
 }
 
 struct Cpu<'a> {
-    clock: usize,
-    regx: i32,
+    state: CpuState,
     devices: Vec<&'a mut dyn OnClockTick>,
 }
 
 impl<'a> Cpu<'a> {
     fn new() -> Self {
+        // Only `x` starts at 1.
+        let mut registers = [0; REGISTERS];
+        registers[X] = 1;
         Cpu {
-            clock: 0,
-            regx: 1,
+            state: CpuState {
+                clock: 0,
+                registers,
+                executing: "",
+                retired: 0,
+            },
             devices: Vec::new(),
         }
     }
@@ -101,21 +166,29 @@ impl<'a> Cpu<'a> {
     }
 
     fn tick(&mut self) {
-        self.clock += 1;
-        self.devices
-            .iter_mut()
-            .for_each(|d| d.sample(self.clock, self.regx));
-    }
-
-    fn execute_instruction(&mut self, instruction: &Instructions) {
-        match instruction {
-            Instructions::Noop => self.tick(),
-            Instructions::Addx(data) => {
-                self.tick();
-                self.tick();
-                self.regx += data;
-            }
+        self.state.clock += 1;
+        self.devices.iter_mut().for_each(|d| d.sample(&self.state));
+    }
+
+    fn execute_instruction(&mut self, instruction: &Instruction) {
+        self.state.executing = instruction.opcode.mnemonic;
+        for _ in 0..instruction.opcode.cycles {
+            self.tick();
         }
+        (instruction.opcode.execute)(&mut self.state.registers, &instruction.args);
+        self.state.retired += 1;
+    }
+}
+
+/// Prints the CPU state at every tick.
+struct Tracer;
+
+impl OnClockTick for Tracer {
+    fn sample(&mut self, state: &CpuState) {
+        println!(
+            "cycle {} ({} retired): {} with registers {:?}",
+            state.clock, state.retired, state.executing, state.registers
+        );
     }
 }
 
@@ -123,16 +196,95 @@ fn main() {
     let mut cpu = Cpu::new();
     let mut crt = Crt::new();
     let mut cpu_probe = CpuProbe::with_sampling_points(vec![20, 60, 100, 140, 180, 220]);
+    let mut tracer = Tracer;
 
     cpu.share_clock_with(&mut crt);
     cpu.share_clock_with(&mut cpu_probe);
+    if std::env::args().any(|arg| arg == "--trace") {
+        cpu.share_clock_with(&mut tracer);
+    }
 
     include_str!("../input").lines().for_each(|line| {
-        let instruction = Instructions::from(line);
+        let instruction = Instruction::from(line);
         cpu.execute_instruction(&instruction);
     });
 
     println!("Part1: {}", cpu_probe.total_signal_strength());
-    println!("Part2:");
-    crt.render_image();
+    match crt.read_letters() {
+        Ok(letters) => println!("Part2: {letters}"),
+        Err(e) => {
+            println!("Part2: {e}");
+            crt.render_image();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const Y: usize = 1;
+
+    static EXTENDED_SET: [Opcode; 3] = [
+        Opcode {
+            mnemonic: "addx",
+            operands: 1,
+            cycles: 2,
+            execute: |registers, args| registers[X] += args[0],
+        },
+        Opcode {
+            mnemonic: "addy",
+            operands: 1,
+            cycles: 3,
+            execute: |registers, args| registers[Y] += args[0],
+        },
+        Opcode {
+            mnemonic: "swap",
+            operands: 0,
+            cycles: 1,
+            execute: |registers, _| registers.swap(X, Y),
+        },
+    ];
+
+    struct Recorder(Vec<CpuState>);
+
+    impl OnClockTick for Recorder {
+        fn sample(&mut self, state: &CpuState) {
+            self.0.push(state.clone());
+        }
+    }
+
+    #[test]
+    fn example_signal_strength() {
+        let mut cpu = Cpu::new();
+        let mut cpu_probe = CpuProbe::with_sampling_points(vec![20, 60, 100, 140, 180, 220]);
+        cpu.share_clock_with(&mut cpu_probe);
+        for line in include_str!("../test-input").lines() {
+            cpu.execute_instruction(&Instruction::from(line));
+        }
+        assert_eq!(cpu_probe.total_signal_strength(), 13140);
+    }
+
+    #[test]
+    fn extra_registers() {
+        let mut cpu = Cpu::new();
+        let mut recorder = Recorder(Vec::new());
+        cpu.share_clock_with(&mut recorder);
+        for line in ["addx 3", "addy -5", "swap"] {
+            cpu.execute_instruction(&Instruction::decode(line, &EXTENDED_SET));
+        }
+
+        let registers = cpu.state.registers;
+        drop(cpu);
+
+        let states = recorder.0;
+        assert_eq!(states.len(), 6);
+        // Results only land once all of an instruction's cycles have passed.
+        assert_eq!(states[1].registers, [1, 0, 0, 0]);
+        assert_eq!(states[2].registers, [4, 0, 0, 0]);
+        assert_eq!(states[4].executing, "addy");
+        assert_eq!(states[5].registers, [4, -5, 0, 0]);
+        assert_eq!(states[5].retired, 2);
+        assert_eq!(registers, [-5, 4, 0, 0]);
+    }
 }
@@ -1,83 +1,127 @@
 use lazy_static::lazy_static;
 use num::integer::lcm;
+use num::{BigUint, Integer};
 use regex::Regex;
 use std::collections::VecDeque;
+use std::fmt::{Display, Write};
 
+/// Anything a monkey can worry about, `u64` or `BigUint` when it gets out of hand.
+trait Worry: Integer + Clone + Display + From<u64> {}
+
+impl<T: Integer + Clone + Display + From<u64>> Worry for T {}
+
+#[derive(Debug, Clone, Copy)]
+enum Operand {
+    Old,
+    Value(u64),
+}
+
+impl From<&str> for Operand {
+    fn from(token: &str) -> Self {
+        match token {
+            "old" => Operand::Old,
+            value => Operand::Value(value.parse().unwrap()),
+        }
+    }
+}
+
+// Only `+` and `*` are parsed. Worry levels are unsigned, so `-` could underflow,
+// and `/` wouldn't keep them congruent modulo the tests' lcm.
 #[derive(Debug)]
-enum Operations {
-    Add(usize),
-    Mul(usize),
-    Sqr,
+enum Operator {
+    Add,
+    Mul,
 }
 
-struct Item {
-    to: usize,
-    item: usize,
+/// `new = <operand> <operator> <operand>`
+#[derive(Debug)]
+struct Operation {
+    left: Operand,
+    operator: Operator,
+    right: Operand,
 }
 
-impl From<&str> for Operations {
+impl From<&str> for Operation {
     fn from(description: &str) -> Self {
-        let raw_op = description
-            .split_whitespace()
-            .rev()
-            .take(2)
-            .collect::<Vec<&str>>();
-        match raw_op[1] {
-            "+" => Operations::Add(raw_op[0].parse::<usize>().unwrap()),
-            "*" => {
-                if let Ok(amt) = raw_op[0].parse::<usize>() {
-                    return Self::Mul(amt);
-                }
-                Self::Sqr
-            }
-            _ => panic!(),
+        let expression = description.split('=').nth(1).unwrap();
+        let tokens = expression.split_whitespace().collect::<Vec<&str>>();
+        let operator = match tokens[1] {
+            "+" => Operator::Add,
+            "*" => Operator::Mul,
+            op => panic!("Unknown operator {op}"),
+        };
+        Operation {
+            left: Operand::from(tokens[0]),
+            operator,
+            right: Operand::from(tokens[2]),
+        }
+    }
+}
+
+impl Operation {
+    fn apply<W: Worry>(&self, old: &W) -> W {
+        let value = |operand: Operand| match operand {
+            Operand::Old => old.clone(),
+            Operand::Value(value) => W::from(value),
+        };
+        let (left, right) = (value(self.left), value(self.right));
+        match self.operator {
+            Operator::Add => left + right,
+            Operator::Mul => left * right,
         }
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+enum Relief {
+    DivideByThree,
+    ModuloLcm,
+    None,
+}
+
+struct Item<W> {
+    to: usize,
+    item: W,
+}
+
 #[derive(Debug)]
-struct Monkey {
-    items: VecDeque<usize>,
-    op: Operations,
-    test_condition: usize,
+struct Monkey<W> {
+    items: VecDeque<W>,
+    op: Operation,
+    test_condition: u64,
     target_true: usize,
     target_false: usize,
     inspected: usize,
 }
 
-impl Monkey {
-    fn inspect_and_throw(&mut self, lcm: usize) -> Vec<Item> {
+impl<W: Worry> Monkey<W> {
+    fn inspect_and_throw(&mut self, relief: Relief, lcm: u64) -> Vec<Item<W>> {
         let mut items = Vec::new();
-        while !self.items.is_empty() {
-            let item = self.items.pop_front().unwrap();
-            let worry = match self.op {
-                Operations::Add(amt) => item + amt,
-                Operations::Mul(amt) => item * amt,
-                Operations::Sqr => item * item,
+        while let Some(item) = self.items.pop_front() {
+            let worry = self.op.apply(&item);
+            let worry = match relief {
+                Relief::DivideByThree => worry / W::from(3),
+                Relief::ModuloLcm => worry % W::from(lcm),
+                Relief::None => worry,
             };
 
-            if worry % self.test_condition == 0 {
-                items.push(Item {
-                    to: self.target_true,
-                    item: worry % lcm,
-                });
+            let to = if (worry.clone() % W::from(self.test_condition)).is_zero() {
+                self.target_true
             } else {
-                items.push(Item {
-                    to: self.target_false,
-                    item: worry % lcm,
-                });
-            }
+                self.target_false
+            };
+            items.push(Item { to, item: worry });
             self.inspected += 1;
         }
         items
     }
 
-    fn catch(&mut self, item: usize) {
+    fn catch(&mut self, item: W) {
         self.items.push_back(item);
     }
 }
 
-impl From<&str> for Monkey {
+impl<W: Worry> From<&str> for Monkey<W> {
     fn from(description: &str) -> Self {
         lazy_static! {
             static ref RE: Regex = Regex::new(r"\d+").unwrap();
@@ -86,17 +130,13 @@ impl From<&str> for Monkey {
         let lines: Vec<&str> = description.lines().collect();
         let items = RE
             .find_iter(lines[1])
-            .filter_map(|digits| digits.as_str().parse().ok())
-            .collect::<VecDeque<usize>>();
+            .filter_map(|digits| digits.as_str().parse::<u64>().ok())
+            .map(W::from)
+            .collect::<VecDeque<W>>();
 
-        let op = Operations::from(lines[2]);
+        let op = Operation::from(lines[2]);
 
-        let test_condition = RE
-            .find(lines[3])
-            .unwrap()
-            .as_str()
-            .parse::<usize>()
-            .unwrap();
+        let test_condition = RE.find(lines[3]).unwrap().as_str().parse::<u64>().unwrap();
         let target_true = RE
             .find(lines[4])
             .unwrap()
@@ -120,31 +160,168 @@ impl From<&str> for Monkey {
     }
 }
 
-fn main() {
-    let mut monkeys = Vec::new();
-    include_str!("../input")
-        .split("\n\n")
-        .for_each(|description| {
-            monkeys.push(Monkey::from(description));
-        });
-
-    let lowest_common_multiple = monkeys
-        .iter()
-        .map(|m| m.test_condition)
-        .reduce(lcm)
-        .unwrap();
-
-    for _ in 0..10000 {
-        for monkey in 0..monkeys.len() {
-            let passed = monkeys[monkey].inspect_and_throw(lowest_common_multiple);
-            passed.iter().for_each(|item| {
-                monkeys[item.to].catch(item.item);
-            })
+struct Simulation<W> {
+    monkeys: Vec<Monkey<W>>,
+    relief: Relief,
+    lcm: u64,
+    round: usize,
+}
+
+impl<W: Worry> Simulation<W> {
+    fn new(notes: &str, relief: Relief) -> Self {
+        let monkeys = notes
+            .split("\n\n")
+            .map(Monkey::from)
+            .collect::<Vec<Monkey<W>>>();
+        let lcm = monkeys
+            .iter()
+            .map(|m| m.test_condition)
+            .reduce(lcm)
+            .unwrap();
+
+        Simulation {
+            monkeys,
+            relief,
+            lcm,
+            round: 0,
         }
     }
 
-    let mut inspected = monkeys.iter().map(|m| m.inspected).collect::<Vec<usize>>();
-    inspected.sort_unstable();
-    inspected.reverse();
-    println!("Part2: {}", inspected[0] * inspected[1]);
+    fn round(&mut self) {
+        for monkey in 0..self.monkeys.len() {
+            let passed = self.monkeys[monkey].inspect_and_throw(self.relief, self.lcm);
+            for item in passed {
+                self.monkeys[item.to].catch(item.item);
+            }
+        }
+        self.round += 1;
+    }
+
+    /// Runs a number of rounds, printing the state after each one when tracing.
+    fn run(&mut self, rounds: usize, trace: bool) {
+        for _ in 0..rounds {
+            self.round();
+            if trace {
+                println!("{}", self.trace());
+            }
+        }
+    }
+
+    /// State of all monkeys, formatted the same way the puzzle text does.
+    fn trace(&self) -> String {
+        let mut trace = format!(
+            "After round {}, the monkeys are holding items with these worry levels:\n",
+            self.round
+        );
+        for (idx, monkey) in self.monkeys.iter().enumerate() {
+            let items = monkey
+                .items
+                .iter()
+                .map(|i| i.to_string())
+                .collect::<Vec<String>>()
+                .join(", ");
+            writeln!(trace, "Monkey {idx}: {items}").unwrap();
+        }
+
+        writeln!(trace, "\n== After round {} ==", self.round).unwrap();
+        for (idx, monkey) in self.monkeys.iter().enumerate() {
+            writeln!(
+                trace,
+                "Monkey {idx} inspected items {} times.",
+                monkey.inspected
+            )
+            .unwrap();
+        }
+        trace
+    }
+
+    fn monkey_business(&self) -> usize {
+        let mut inspected = self
+            .monkeys
+            .iter()
+            .map(|m| m.inspected)
+            .collect::<Vec<usize>>();
+        inspected.sort_unstable();
+        inspected.reverse();
+        inspected[0] * inspected[1]
+    }
+}
+
+fn main() {
+    let notes = include_str!("../input");
+
+    let mut part_1 = Simulation::<u64>::new(notes, Relief::DivideByThree);
+    part_1.run(20, false);
+    println!("Part1: {}", part_1.monkey_business());
+
+    let mut part_2 = Simulation::<u64>::new(notes, Relief::ModuloLcm);
+    part_2.run(10000, false);
+    println!("Part2: {}", part_2.monkey_business());
+
+    // Without any relief worry levels explode, so only a single round.
+    if std::env::args().any(|arg| arg == "--trace") {
+        let mut unrelieved = Simulation::<BigUint>::new(notes, Relief::None);
+        println!();
+        unrelieved.run(1, true);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = include_str!("../test-input");
+
+    #[test]
+    fn example_monkey_business() {
+        let mut part_1 = Simulation::<u64>::new(EXAMPLE, Relief::DivideByThree);
+        part_1.run(20, false);
+        assert_eq!(part_1.monkey_business(), 10605);
+
+        let mut part_2 = Simulation::<u64>::new(EXAMPLE, Relief::ModuloLcm);
+        part_2.run(10000, false);
+        assert_eq!(part_2.monkey_business(), 2713310158);
+    }
+
+    #[test]
+    fn trace_matches_puzzle() {
+        let mut part_1 = Simulation::<u64>::new(EXAMPLE, Relief::DivideByThree);
+        part_1.run(1, false);
+        let trace = part_1.trace();
+        assert!(trace.starts_with(
+            "After round 1, the monkeys are holding items with these worry levels:\n\
+             Monkey 0: 20, 23, 27, 26\n\
+             Monkey 1: 2080, 25, 167, 207, 401, 1046\n\
+             Monkey 2: \n\
+             Monkey 3: \n"
+        ));
+
+        part_1.run(19, false);
+        assert!(part_1
+            .trace()
+            .contains("Monkey 0: 10, 12, 14, 26, 34\nMonkey 1: 245, 93, 53, 199, 115\n"));
+    }
+
+    #[test]
+    fn trace_without_relief() {
+        // Without any relief worry levels explode, hence the big integers.
+        let mut example = Simulation::<BigUint>::new(EXAMPLE, Relief::None);
+        example.run(1, false);
+        assert!(example.trace().ends_with(
+            "== After round 1 ==\n\
+             Monkey 0 inspected items 2 times.\n\
+             Monkey 1 inspected items 4 times.\n\
+             Monkey 2 inspected items 3 times.\n\
+             Monkey 3 inspected items 6 times.\n"
+        ));
+
+        example.run(19, false);
+        assert!(example.trace().ends_with(
+            "== After round 20 ==\n\
+             Monkey 0 inspected items 99 times.\n\
+             Monkey 1 inspected items 97 times.\n\
+             Monkey 2 inspected items 8 times.\n\
+             Monkey 3 inspected items 103 times.\n"
+        ));
+    }
 }
@@ -1,35 +1,151 @@
-use itertools::EitherOrBoth::*;
-use itertools::Itertools;
-use serde::Deserialize;
 use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
 
-#[derive(Deserialize, PartialEq, Eq)]
-#[serde(untagged)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 enum Packet {
-    Val(u8),
+    Val(u64),
     Arr(Vec<Packet>),
 }
 
+#[derive(Debug)]
+struct ParseError {
+    offset: usize,
+    message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.offset)
+    }
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error<T>(&self, message: String) -> Result<T, ParseError> {
+        Err(ParseError {
+            offset: self.pos,
+            message,
+        })
+    }
+
+    fn unexpected<T>(&self, expected: &str) -> Result<T, ParseError> {
+        match self.bytes.get(self.pos) {
+            Some(byte) => self.error(format!(
+                "Expected {expected}, found '{}'",
+                char::from(*byte)
+            )),
+            None => self.error(format!("Expected {expected}, found end of input")),
+        }
+    }
+
+    fn packet(&mut self) -> Result<Packet, ParseError> {
+        match self.bytes.get(self.pos) {
+            Some(b'[') => self.list(),
+            Some(b'0'..=b'9') => self.value(),
+            _ => self.unexpected("'[' or a digit"),
+        }
+    }
+
+    fn list(&mut self) -> Result<Packet, ParseError> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        if self.bytes.get(self.pos) == Some(&b']') {
+            self.pos += 1;
+            return Ok(Packet::Arr(items));
+        }
+
+        loop {
+            items.push(self.packet()?);
+            match self.bytes.get(self.pos) {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Packet::Arr(items));
+                }
+                _ => return self.unexpected("',' or ']'"),
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Packet, ParseError> {
+        let start = self.pos;
+        let mut value: u64 = 0;
+        while let Some(digit @ b'0'..=b'9') = self.bytes.get(self.pos) {
+            value = match value
+                .checked_mul(10)
+                .and_then(|v| v.checked_add((digit - b'0') as u64))
+            {
+                Some(value) => value,
+                None => {
+                    self.pos = start;
+                    return self.error("Value does not fit in 64 bits".to_string());
+                }
+            };
+            self.pos += 1;
+        }
+        Ok(Packet::Val(value))
+    }
+}
+
+impl FromStr for Packet {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            bytes: s.as_bytes(),
+            pos: 0,
+        };
+        let packet = parser.packet()?;
+        if parser.pos != s.len() {
+            return parser.unexpected("end of input");
+        }
+        Ok(packet)
+    }
+}
+
+impl fmt::Display for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Packet::Val(value) => write!(f, "{value}"),
+            Packet::Arr(items) => {
+                write!(f, "[")?;
+                for (idx, item) in items.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+fn compare_lists(left: &[Packet], right: &[Packet]) -> Ordering {
+    for (a, b) in left.iter().zip(right) {
+        let ord = a.cmp(b);
+        if !ord.is_eq() {
+            return ord;
+        }
+    }
+    left.len().cmp(&right.len())
+}
+
 impl Ord for Packet {
     fn cmp(&self, other: &Self) -> Ordering {
         use Packet::*;
+        // A value compared to a list acts as a list of just itself, which a
+        // single element slice gives us without building a new packet.
         match (self, other) {
             (Val(left), Val(right)) => left.cmp(right),
-            (Val(left), Arr(_)) => Arr(vec![Val(*left)]).cmp(other),
-            (Arr(_), Val(right)) => self.cmp(&Arr(vec![Val(*right)])),
-            (Arr(left), Arr(right)) => {
-                for lr in left.iter().zip_longest(right.iter()) {
-                    let ord = match lr {
-                        Left(_) => Ordering::Greater,
-                        Right(_) => Ordering::Less,
-                        Both(a, b) => a.cmp(b),
-                    };
-                    if !ord.is_eq() {
-                        return ord;
-                    }
-                }
-                Ordering::Equal
-            }
+            (Val(_), Arr(right)) => compare_lists(std::slice::from_ref(self), right),
+            (Arr(left), Val(_)) => compare_lists(left, std::slice::from_ref(other)),
+            (Arr(left), Arr(right)) => compare_lists(left, right),
         }
     }
 }
@@ -40,17 +156,43 @@ impl PartialOrd for Packet {
     }
 }
 
+/// Packets kept in the right order as they come in.
+#[derive(Default)]
+struct SortedPackets(Vec<Packet>);
+
+impl SortedPackets {
+    /// Inserts a packet after any equal ones, returning its index.
+    fn insert(&mut self, packet: Packet) -> usize {
+        let idx = self.0.partition_point(|p| p <= &packet);
+        self.0.insert(idx, packet);
+        idx
+    }
+
+    /// Index of the last packet identical to `packet`, which is where `insert`
+    /// put it. Packets like `[2]` and `[[2]]` compare equal without being the
+    /// same, so the whole equal range has to be searched.
+    fn position(&self, packet: &Packet) -> Option<usize> {
+        let start = self.0.partition_point(|p| p < packet);
+        let end = self.0.partition_point(|p| p <= packet);
+        self.0[start..end]
+            .iter()
+            .rposition(|p| p == packet)
+            .map(|idx| start + idx)
+    }
+}
+
+fn parse(row: &str) -> Packet {
+    row.parse::<Packet>()
+        .unwrap_or_else(|e| panic!("Bad packet {row}: {e}"))
+}
+
 fn part1(data: &str) {
     let ans = data
         .split("\n\n")
         .enumerate()
         .filter(|(_, pair)| {
-            let (left, right) = pair
-                .split('\n')
-                .map(|row| serde_json::from_str::<Packet>(row).unwrap())
-                .collect_tuple::<(Packet, Packet)>()
-                .unwrap();
-            left < right
+            let (left, right) = pair.split_once('\n').unwrap();
+            parse(left) < parse(right.trim_end())
         })
         .map(|(index, _)| index + 1)
         .sum::<usize>();
@@ -58,26 +200,19 @@ fn part1(data: &str) {
 }
 
 fn part2(data: &str) {
-    use Packet::*;
-    let mut packets: Vec<Packet> = Vec::new();
+    let mut packets = SortedPackets::default();
     data.lines().filter(|l| !l.is_empty()).for_each(|row| {
-        let packet = serde_json::from_str::<Packet>(row).unwrap();
-        packets.push(packet);
+        packets.insert(parse(row));
     });
 
-    packets.push(serde_json::from_str::<Packet>("[[2]]").unwrap());
-    packets.push(serde_json::from_str::<Packet>("[[6]]").unwrap());
-    packets.sort_unstable();
+    let dividers = [parse("[[2]]"), parse("[[6]]")];
+    for divider in &dividers {
+        packets.insert(divider.clone());
+    }
 
-    let ans: usize = [2, 6]
+    let ans: usize = dividers
         .iter()
-        .map(|divider| {
-            packets
-                .iter()
-                .filter(|p| *p < &Arr(vec![Arr(vec![Val(*divider)])]))
-                .count()
-                + 1
-        })
+        .map(|divider| packets.position(divider).unwrap() + 1)
         .product();
     println!("Part2: {}", ans);
 }
@@ -87,3 +222,56 @@ fn main() {
     part1(data);
     part2(data);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_round_trips() {
+        for line in include_str!("../test-input")
+            .lines()
+            .filter(|l| !l.is_empty())
+        {
+            assert_eq!(parse(line).to_string(), line);
+        }
+    }
+
+    #[test]
+    fn reports_byte_offsets() {
+        let cases = [
+            (
+                "[1,[2,3]",
+                "Expected ',' or ']', found end of input at byte 8",
+            ),
+            ("[1,,2]", "Expected '[' or a digit, found ',' at byte 3"),
+            (
+                "[18446744073709551616]",
+                "Value does not fit in 64 bits at byte 1",
+            ),
+            ("[1]]", "Expected end of input, found ']' at byte 3"),
+        ];
+        for (broken, message) in cases {
+            assert_eq!(broken.parse::<Packet>().unwrap_err().to_string(), message);
+        }
+    }
+
+    #[test]
+    fn position_matches_insert() {
+        let mut packets = SortedPackets::default();
+        for row in ["[2]", "[[2]]", "[1]", "[[[2]]]"] {
+            packets.insert(parse(row));
+        }
+        let idx = packets.insert(parse("[[2]]"));
+        assert_eq!(packets.position(&parse("[[2]]")), Some(idx));
+        assert_eq!(packets.position(&parse("[3]")), None);
+
+        // Equal by ordering, but not the same packet.
+        let mut packets = SortedPackets::default();
+        let nested = packets.insert(parse("[[2]]"));
+        packets.insert(parse("[2]"));
+        assert_eq!(packets.position(&parse("[[2]]")), Some(nested));
+        assert_eq!(packets.position(&parse("[2]")), Some(nested + 1));
+        assert_eq!(packets.position(&parse("[[[2]]]")), None);
+    }
+}
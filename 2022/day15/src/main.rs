@@ -1,10 +1,105 @@
 use lazy_static::lazy_static;
 use regex::Regex;
-use std::cmp::{max, min};
+use std::cmp::max;
 use std::collections::HashSet;
 
 struct Beacon(i64, i64);
 
+/// Sorted set of disjoint, non-adjacent inclusive integer intervals.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct IntervalSet(Vec<(i64, i64)>);
+
+impl IntervalSet {
+    fn single(lo: i64, hi: i64) -> Self {
+        if lo > hi {
+            return Self::default();
+        }
+        Self(vec![(lo, hi)])
+    }
+
+    fn union(&self, other: &IntervalSet) -> Self {
+        let mut merged: Vec<(i64, i64)> = Vec::with_capacity(self.0.len() + other.0.len());
+        let (mut a, mut b) = (self.0.iter().peekable(), other.0.iter().peekable());
+        loop {
+            let next = match (a.peek(), b.peek()) {
+                (Some(x), Some(y)) if x.0 <= y.0 => a.next(),
+                (Some(_), Some(_)) => b.next(),
+                (Some(_), None) => a.next(),
+                (None, Some(_)) => b.next(),
+                (None, None) => break,
+            };
+
+            let &(lo, hi) = next.unwrap();
+            match merged.last_mut() {
+                Some(last) if lo <= last.1 + 1 => last.1 = max(last.1, hi),
+                _ => merged.push((lo, hi)),
+            }
+        }
+        Self(merged)
+    }
+
+    /// Unions sets pairwise, so that k sets merge in O(k log k).
+    fn union_all(mut sets: Vec<IntervalSet>) -> Self {
+        while sets.len() > 1 {
+            sets = sets
+                .chunks(2)
+                .map(|pair| match pair {
+                    [a, b] => a.union(b),
+                    [a] => a.clone(),
+                    _ => unreachable!(),
+                })
+                .collect();
+        }
+        sets.pop().unwrap_or_default()
+    }
+
+    fn subtract(&self, other: &IntervalSet) -> Self {
+        let mut remaining = Vec::new();
+        let mut cuts = other.0.iter().peekable();
+        for &(lo, hi) in &self.0 {
+            let mut start = lo;
+            while let Some(&&(cut_lo, cut_hi)) = cuts.peek() {
+                if cut_hi < start {
+                    cuts.next();
+                    continue;
+                }
+                if cut_lo > hi {
+                    break;
+                }
+                if cut_lo > start {
+                    remaining.push((start, cut_lo - 1));
+                }
+                start = cut_hi + 1;
+                if cut_hi > hi {
+                    break;
+                }
+                cuts.next();
+            }
+            if start <= hi {
+                remaining.push((start, hi));
+            }
+        }
+        Self(remaining)
+    }
+
+    /// Parts of `lo..=hi` this set does not cover.
+    fn gaps(&self, lo: i64, hi: i64) -> Self {
+        Self::single(lo, hi).subtract(self)
+    }
+
+    fn len(&self) -> i64 {
+        self.0.iter().map(|(lo, hi)| hi - lo + 1).sum()
+    }
+}
+
+impl FromIterator<i64> for IntervalSet {
+    fn from_iter<T: IntoIterator<Item = i64>>(iter: T) -> Self {
+        let mut points = iter.into_iter().collect::<Vec<i64>>();
+        points.sort_unstable();
+        Self::union_all(points.into_iter().map(|p| Self::single(p, p)).collect())
+    }
+}
+
 #[derive(PartialEq, Eq, Hash)]
 struct Sensor {
     x: i64,
@@ -17,61 +112,80 @@ impl Sensor {
         let dist = (x - beacon.0).abs() + (y - beacon.1).abs();
         Sensor { x, y, dist }
     }
+
+    fn covers(&self, x: i64, y: i64) -> bool {
+        (self.x - x).abs() + (self.y - y).abs() <= self.dist
+    }
+
+    fn row_span(&self, row: i64) -> IntervalSet {
+        let reach = self.dist - (self.y - row).abs();
+        IntervalSet::single(self.x - reach, self.x + reach)
+    }
+}
+
+enum Search {
+    RowSweep,
+    LineIntersection,
 }
 
 struct Scan {
     sensors: HashSet<Sensor>,
+    beacons: HashSet<(i64, i64)>,
 }
 
 impl Scan {
+    fn covered_on_row(&self, row: i64) -> IntervalSet {
+        IntervalSet::union_all(self.sensors.iter().map(|s| s.row_span(row)).collect())
+    }
+
     fn coverage(&self, row: i64) -> i64 {
-        let mut covers = Vec::new();
-        for sensor in &self.sensors {
-            for dx in 0..sensor.dist {
-                let dy = sensor.dist - dx;
-                if (sensor.y + dy) == row || (sensor.y - dy) == row {
-                    covers.push((sensor.x - dx, sensor.x + dx));
-                    break;
-                }
-            }
-        }
+        let beacons = self
+            .beacons
+            .iter()
+            .filter(|b| b.1 == row)
+            .map(|b| b.0)
+            .collect::<IntervalSet>();
+        self.covered_on_row(row).subtract(&beacons).len()
+    }
+
+    fn is_uncovered(&self, x: i64, y: i64) -> bool {
+        self.sensors.iter().all(|s| !s.covers(x, y))
+    }
 
-        let rng = covers
-            .into_iter()
-            .reduce(|a, b| (min(a.0, b.0), max(a.1, b.1)))
-            .unwrap();
-        rng.1 - rng.0
-    }
-
-    fn check_outside_bounds(&self) -> i64 {
-        let bound = 4000000;
-        for sensor in &self.sensors {
-            for dx in 0..=sensor.dist + 1 {
-                let dy = sensor.dist + 1 - dx;
-                for comb in [(1, 1), (-1, 1), (-1, -1), (1, -1)] {
-                    let x = dx * comb.0;
-                    let y = dy * comb.1;
-                    let mut this = true;
-                    for other in &self.sensors {
-                        if (other.x - (sensor.x + x)).abs() + (other.y - (sensor.y + y)).abs()
-                            <= other.dist
-                        {
-                            this = false;
-                            break;
-                        }
-                    }
-                    if this
-                        && sensor.x + x <= bound
-                        && sensor.x + x >= 0
-                        && sensor.y + y <= bound
-                        && sensor.y + y >= 0
-                    {
-                        return ((sensor.x + x) * 4000000) + sensor.y + y;
-                    }
+    fn find_distress_beacon(&self, bound: i64, search: Search) -> Option<(i64, i64)> {
+        match search {
+            Search::RowSweep => (0..=bound).find_map(|row| {
+                let gaps = self.covered_on_row(row).gaps(0, bound);
+                gaps.0.first().map(|gap| (gap.0, row))
+            }),
+            Search::LineIntersection => {
+                // In rotated coordinates u = x + y, v = x - y every sensor range is a square,
+                // so a lone uncovered cell sits where a u-line and a v-line just outside
+                // the ranges cross (or in a corner of the search area).
+                let mut us = Vec::new();
+                let mut vs = Vec::new();
+                for s in &self.sensors {
+                    us.extend([s.x + s.y - s.dist - 1, s.x + s.y + s.dist + 1]);
+                    vs.extend([s.x - s.y - s.dist - 1, s.x - s.y + s.dist + 1]);
                 }
+
+                let corners = [(0, 0), (0, bound), (bound, 0), (bound, bound)];
+                let crossings = us.iter().flat_map(|u| {
+                    vs.iter()
+                        .filter(move |v| (u - *v) % 2 == 0)
+                        .map(move |v| ((u + v) / 2, (u - v) / 2))
+                });
+
+                corners.into_iter().chain(crossings).find(|&(x, y)| {
+                    (0..=bound).contains(&x) && (0..=bound).contains(&y) && self.is_uncovered(x, y)
+                })
             }
         }
-        -1
+    }
+
+    fn tuning_frequency(&self, bound: i64, search: Search) -> Option<i64> {
+        self.find_distress_beacon(bound, search)
+            .map(|(x, y)| x * 4000000 + y)
     }
 }
 
@@ -82,6 +196,7 @@ impl From<&str> for Scan {
         }
 
         let mut sensors = HashSet::new();
+        let mut beacons = HashSet::new();
         report.lines().for_each(|line| {
             let split = line.split(':').collect::<Vec<&str>>();
             let sensor = RE
@@ -95,9 +210,10 @@ impl From<&str> for Scan {
 
             let s = Sensor::with_beacon(sensor[0], sensor[1], &Beacon(beacon[0], beacon[1]));
             sensors.insert(s);
+            beacons.insert((beacon[0], beacon[1]));
         });
 
-        Scan { sensors }
+        Scan { sensors, beacons }
     }
 }
 
@@ -105,5 +221,34 @@ fn main() {
     let data = include_str!("../input");
     let scan = Scan::from(data);
     println!("Part1: {}", scan.coverage(2000000));
-    println!("Part2: {}", scan.check_outside_bounds());
+    let bound = 4000000;
+    // Row sweeps are much slower, but don't rely on every gap being a lone cell.
+    let search = if std::env::args().any(|arg| arg == "--row-sweep") {
+        Search::RowSweep
+    } else {
+        Search::LineIntersection
+    };
+    match scan.tuning_frequency(bound, search) {
+        Some(frequency) => println!("Part2: {frequency}"),
+        None => println!("Part2: no distress beacon in 0..={bound}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn searches_agree_on_example() {
+        let example = Scan::from(include_str!("../test-input"));
+        assert_eq!(example.coverage(10), 26);
+        assert_eq!(
+            example.tuning_frequency(20, Search::RowSweep),
+            Some(56000011)
+        );
+        assert_eq!(
+            example.tuning_frequency(20, Search::LineIntersection),
+            Some(56000011)
+        );
+    }
 }
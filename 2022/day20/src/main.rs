@@ -1,43 +1,221 @@
-#[derive(Clone)]
-struct Value {
-    index: usize,
-    value: i64,
+/// Circular list of numbers that can be mixed in O(sqrt n) per move. Elements are
+/// kept in blocks of roughly sqrt n original indices, so finding, removing and
+/// inserting only ever scan a block's worth of elements plus the block sizes.
+struct CircularMixer {
+    values: Vec<i64>,
+    blocks: Vec<Vec<usize>>,
+    // Which block each original index currently lives in.
+    block_of: Vec<usize>,
+    block_size: usize,
 }
 
-impl Value {
-    fn with_original_index(index: usize, value: i64) -> Self {
-        Value { index, value }
+impl CircularMixer {
+    fn new(values: Vec<i64>) -> Self {
+        let block_size = ((values.len() as f64).sqrt() as usize).max(1);
+        let mut mixer = CircularMixer {
+            blocks: Vec::new(),
+            block_of: vec![0; values.len()],
+            values,
+            block_size,
+        };
+        mixer.rebuild((0..mixer.values.len()).collect());
+        mixer
     }
-}
 
-fn prepare_for_mixing(data: &str, key: i64) -> Vec<Value> {
-    data.lines()
-        .enumerate()
-        .map(|(index, line)| Value::with_original_index(index, line.parse::<i64>().unwrap() * key))
-        .collect::<Vec<Value>>()
-}
+    fn rebuild(&mut self, order: Vec<usize>) {
+        self.blocks = order
+            .chunks(self.block_size)
+            .map(|chunk| chunk.to_vec())
+            .collect();
+        for (block, indices) in self.blocks.iter().enumerate() {
+            for idx in indices {
+                self.block_of[*idx] = block;
+            }
+        }
+    }
+
+    fn order(&self) -> Vec<usize> {
+        self.blocks.iter().flatten().copied().collect()
+    }
 
-fn mix(mut values: Vec<Value>, n_iter: usize) -> i64 {
-    let file_len = (values.len() as i64) - 1;
-    for _ in 0..n_iter {
-        for idx in 0..=file_len {
-            let curridx = values.iter().position(|v| v.index == idx as usize).unwrap();
-            let val = values.remove(curridx);
-            let newidx = ((curridx as i64 + val.value % file_len) + file_len) % file_len;
-            values.insert(newidx as usize, val);
+    fn position(&self, idx: usize) -> usize {
+        let block = self.block_of[idx];
+        let before = self.blocks[..block].iter().map(Vec::len).sum::<usize>();
+        before + self.blocks[block].iter().position(|i| *i == idx).unwrap()
+    }
+
+    fn at(&self, mut pos: usize) -> usize {
+        for block in &self.blocks {
+            if pos < block.len() {
+                return block[pos];
+            }
+            pos -= block.len();
         }
+        unreachable!()
+    }
+
+    fn remove(&mut self, idx: usize) -> usize {
+        let pos = self.position(idx);
+        let block = &mut self.blocks[self.block_of[idx]];
+        let offset = block.iter().position(|i| *i == idx).unwrap();
+        block.remove(offset);
+        pos
     }
 
-    let zeropos = values.iter().position(|v| v.value == 0).unwrap();
-    [1000, 2000, 3000]
-        .iter()
-        .map(|v| values[(zeropos + v) % values.len()].clone())
-        .map(|v| v.value)
-        .sum()
+    fn insert(&mut self, mut pos: usize, idx: usize) {
+        let last = self.blocks.len() - 1;
+        for (block, indices) in self.blocks.iter_mut().enumerate() {
+            if pos <= indices.len() && (pos < indices.len() || block == last) {
+                indices.insert(pos, idx);
+                self.block_of[idx] = block;
+                break;
+            }
+            pos -= indices.len();
+        }
+
+        if self.blocks[self.block_of[idx]].len() > self.block_size * 2 {
+            self.rebuild(self.order());
+        }
+    }
+
+    fn mix(&mut self, rounds: usize) {
+        // Nothing can move with a single value, and it would be modulo zero.
+        if self.values.len() < 2 {
+            return;
+        }
+
+        let file_len = self.values.len() as i64 - 1;
+        for _ in 0..rounds {
+            for idx in 0..self.values.len() {
+                let curridx = self.remove(idx) as i64;
+                let newidx = (curridx + self.values[idx]).rem_euclid(file_len);
+                self.insert(newidx as usize, idx);
+            }
+        }
+    }
+
+    /// Sum of the values found at each offset after the zero.
+    fn grove_coordinates(&self, offsets: &[usize]) -> i64 {
+        let zero = self.values.iter().position(|v| *v == 0).unwrap();
+        let zeropos = self.position(zero);
+        offsets
+            .iter()
+            .map(|offset| self.values[self.at((zeropos + offset) % self.values.len())])
+            .sum()
+    }
+}
+
+fn prepare_for_mixing(data: &str, key: i64) -> CircularMixer {
+    CircularMixer::new(
+        data.lines()
+            .map(|line| line.parse::<i64>().unwrap() * key)
+            .collect(),
+    )
 }
 
 fn main() {
     let data = include_str!("../input");
-    println!("Part1: {}", mix(prepare_for_mixing(data, 1), 1));
-    println!("Part1: {}", mix(prepare_for_mixing(data, 811589153), 10));
+
+    let mut part_1 = prepare_for_mixing(data, 1);
+    part_1.mix(1);
+    println!("Part1: {}", part_1.grove_coordinates(&[1000, 2000, 3000]));
+
+    let mut part_2 = prepare_for_mixing(data, 811589153);
+    part_2.mix(10);
+    println!("Part2: {}", part_2.grove_coordinates(&[1000, 2000, 3000]));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = include_str!("../test-input");
+
+    /// Mixes the obvious way, moving indices around in a single `Vec`.
+    fn mix_naively(values: &[i64], rounds: usize) -> Vec<i64> {
+        let mut order = (0..values.len()).collect::<Vec<usize>>();
+        for _ in 0..rounds {
+            for idx in 0..values.len() {
+                let pos = order.iter().position(|i| *i == idx).unwrap();
+                order.remove(pos);
+                let newpos = (pos as i64 + values[idx]).rem_euclid(values.len() as i64 - 1);
+                order.insert(newpos as usize, idx);
+            }
+        }
+        order.into_iter().map(|idx| values[idx]).collect()
+    }
+
+    fn mixed_values(mixer: &CircularMixer) -> Vec<i64> {
+        mixer
+            .order()
+            .into_iter()
+            .map(|idx| mixer.values[idx])
+            .collect()
+    }
+
+    #[test]
+    fn example() {
+        let mut part_1 = prepare_for_mixing(EXAMPLE, 1);
+        part_1.mix(1);
+        // The puzzle's 1, 2, -3, 4, 0, 3, -2, starting from a different spot.
+        assert_eq!(mixed_values(&part_1), vec![-2, 1, 2, -3, 4, 0, 3]);
+        assert_eq!(part_1.grove_coordinates(&[1000, 2000, 3000]), 3);
+        assert_eq!(part_1.grove_coordinates(&[1000]), 4);
+        assert_eq!(part_1.grove_coordinates(&[0, 1, 2]), 1);
+        assert_eq!(part_1.grove_coordinates(&[3, 7]), 1);
+
+        let mut part_2 = prepare_for_mixing(EXAMPLE, 811589153);
+        part_2.mix(10);
+        assert_eq!(part_2.grove_coordinates(&[1000, 2000, 3000]), 1623178306);
+    }
+
+    #[test]
+    fn rebuilds_crowded_blocks() {
+        let mut mixer = CircularMixer::new((0..16).collect());
+        assert_eq!(mixer.block_size, 4);
+        // Crowd the first block until it holds more than two blocks' worth.
+        for idx in 8..13 {
+            mixer.remove(idx);
+            mixer.insert(idx - 7, idx);
+        }
+        assert!(mixer.blocks.iter().all(|block| block.len() <= 4));
+        assert_eq!(
+            mixer.order(),
+            vec![0, 8, 9, 10, 11, 12, 1, 2, 3, 4, 5, 6, 7, 13, 14, 15]
+        );
+        for (pos, idx) in mixer.order().into_iter().enumerate() {
+            assert_eq!(mixer.position(idx), pos);
+            assert_eq!(mixer.at(pos), idx);
+        }
+    }
+
+    #[test]
+    fn long_run_matches_naive_mixing() {
+        let mut seed = 20221220u64;
+        let mut values = (0..200)
+            .map(|_| {
+                seed = seed
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (seed >> 33) as i64 % 10_000 - 5_000
+            })
+            .map(|v| if v == 0 { 1 } else { v * 811589153 })
+            .collect::<Vec<i64>>();
+        values[17] = 0;
+
+        let mut mixer = CircularMixer::new(values.clone());
+        mixer.mix(10);
+        assert_eq!(mixed_values(&mixer), mix_naively(&values, 10));
+    }
+
+    #[test]
+    fn tiny_files() {
+        let mut single = CircularMixer::new(vec![0]);
+        single.mix(3);
+        assert_eq!(single.grove_coordinates(&[1000, 2000, 3000]), 0);
+
+        let mut empty = CircularMixer::new(Vec::new());
+        empty.mix(1);
+        assert!(empty.order().is_empty());
+    }
 }
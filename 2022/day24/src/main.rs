@@ -1,142 +1,199 @@
 use std::collections::VecDeque;
 
-static OFFSETS: &[(i32, i32); 4] = &[(1, 0), (0, 1), (-1, 0), (0, -1)];
-
-#[derive(PartialEq)]
-enum Directions {
-    Up,
-    Down,
-    Left,
-    Right,
-}
+static OFFSETS: &[(i32, i32); 5] = &[(1, 0), (0, 1), (-1, 0), (0, -1), (0, 0)];
 
-#[derive(PartialEq)]
-struct Blizzard {
-    position: (i32, i32),
-    direction: Directions,
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
 }
 
-impl PartialEq<(i32, i32)> for Blizzard {
-    fn eq(&self, other: &(i32, i32)) -> bool {
-        self.position.0 == other.0 && self.position.1 == other.1
+/// Rotates the lowest `width` bits of `mask` towards lower bits by `shift`.
+fn rotate_down(mask: u128, shift: usize, width: usize) -> u128 {
+    let shift = shift % width;
+    if shift == 0 {
+        return mask;
     }
+    let full = u128::MAX >> (128 - width);
+    ((mask >> shift) | (mask << (width - shift))) & full
 }
 
-impl Blizzard {
-    fn with_direction(position: (i32, i32), direction: &char) -> Self {
-        match direction {
-            '^' => Blizzard {
-                position,
-                direction: Directions::Up,
-            },
-            'v' => Blizzard {
-                position,
-                direction: Directions::Down,
-            },
-            '<' => Blizzard {
-                position,
-                direction: Directions::Left,
-            },
-            '>' => Blizzard {
-                position,
-                direction: Directions::Right,
-            },
-            _ => unreachable!(),
-        }
-    }
+struct Valley {
+    height: usize,
+    width: usize,
+    period: usize,
+    // occupied[t][row] has a bit set for every column a blizzard covers at time t.
+    occupied: Vec<Vec<u128>>,
+}
 
-    fn update_position(&mut self, bounds: &(i32, i32)) {
-        let maxrow = bounds.0 + 1;
-        let maxcol = bounds.1 + 1;
-        match self.direction {
-            Directions::Up => {
-                let newrow = ((self.position.0 - 1 % maxrow) + maxrow) % maxrow;
-                self.position = (newrow, self.position.1);
-            }
-            Directions::Down => {
-                let newrow = (self.position.0 + 1) % maxrow;
-                self.position = (newrow, self.position.1);
-            }
-            Directions::Left => {
-                let newcol = ((self.position.1 - 1 % maxcol) + maxcol) % maxcol;
-                self.position = (self.position.0, newcol);
-            }
-            Directions::Right => {
-                let newcol = (self.position.1 + 1) % maxcol;
-                self.position = (self.position.0, newcol);
+impl From<&str> for Valley {
+    fn from(raw_map: &str) -> Self {
+        let lines = raw_map.lines().collect::<Vec<&str>>();
+        let height = lines.len() - 2;
+        let width = lines[0].len() - 2;
+        assert!(
+            width <= 128 && height <= 128,
+            "Valley too big for u128 bitsets"
+        );
+
+        // Horizontal blizzards never leave their row, vertical ones their column.
+        let mut left = vec![0u128; height];
+        let mut right = vec![0u128; height];
+        let mut up = vec![0u128; width];
+        let mut down = vec![0u128; width];
+        for (row, line) in lines[1..=height].iter().enumerate() {
+            for (col, char) in line.chars().skip(1).take(width).enumerate() {
+                match char {
+                    '<' => left[row] |= 1 << col,
+                    '>' => right[row] |= 1 << col,
+                    '^' => up[col] |= 1 << row,
+                    'v' => down[col] |= 1 << row,
+                    _ => {}
+                }
             }
         }
+
+        let period = width * height / gcd(width, height);
+        let occupied = (0..period)
+            .map(|t| {
+                let ups = up
+                    .iter()
+                    .map(|m| rotate_down(*m, t, height))
+                    .collect::<Vec<u128>>();
+                let downs = down
+                    .iter()
+                    .map(|m| rotate_down(*m, height - t % height, height))
+                    .collect::<Vec<u128>>();
+
+                (0..height)
+                    .map(|row| {
+                        let mut mask = rotate_down(left[row], t, width)
+                            | rotate_down(right[row], width - t % width, width);
+                        for col in 0..width {
+                            if (ups[col] | downs[col]) & (1 << row) != 0 {
+                                mask |= 1 << col;
+                            }
+                        }
+                        mask
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Valley {
+            height,
+            width,
+            period,
+            occupied,
+        }
     }
 }
 
-fn traverse(blizzards: &mut [Blizzard], start: (i32, i32), exit: (i32, i32)) -> i32 {
-    let mut minute = 0;
-    let bounds = find_exit(blizzards);
-    let mut to_visit = VecDeque::new();
-    to_visit.push_back(start);
-
-    loop {
-        minute += 1;
-        advance_blizzards(blizzards, &bounds);
-        let mut next_to_visit = VecDeque::new();
-        while let Some(curr_pos) = to_visit.pop_front() {
+impl Valley {
+    fn entrance(&self) -> (i32, i32) {
+        (-1, 0)
+    }
+
+    fn exit(&self) -> (i32, i32) {
+        (self.height as i32, self.width as i32 - 1)
+    }
+
+    fn is_free(&self, pos: (i32, i32), time: usize) -> bool {
+        if pos == self.entrance() || pos == self.exit() {
+            return true;
+        }
+        if pos.0 < 0 || pos.1 < 0 || pos.0 >= self.height as i32 || pos.1 >= self.width as i32 {
+            return false;
+        }
+        self.occupied[time % self.period][pos.0 as usize] & (1 << pos.1) == 0
+    }
+
+    fn state_index(&self, pos: (i32, i32), time: usize) -> usize {
+        // Rows are shifted by one to make room for the entrance row.
+        let cell = (pos.0 + 1) as usize * self.width + pos.1 as usize;
+        (time % self.period) * (self.height + 2) * self.width + cell
+    }
+
+    /// BFS over (position, time mod period), returns the time of arrival.
+    fn traverse(&self, start: (i32, i32), target: (i32, i32), start_time: usize) -> Option<usize> {
+        let mut seen = vec![false; self.period * (self.height + 2) * self.width];
+        let mut to_visit = VecDeque::from([(start, start_time)]);
+        seen[self.state_index(start, start_time)] = true;
+
+        while let Some((pos, time)) = to_visit.pop_front() {
+            if pos == target {
+                return Some(time);
+            }
+
             for offset in OFFSETS.iter() {
-                let cand = (curr_pos.0 + offset.0, curr_pos.1 + offset.1);
-                if cand.0 < 0 || cand.1 < 0 || cand.1 > bounds.1 || cand.0 > bounds.0 {
+                let cand = (pos.0 + offset.0, pos.1 + offset.1);
+                if !self.is_free(cand, time + 1) {
                     continue;
                 }
 
-                if !blizzards.iter().any(|b| b == &cand) {
-                    if cand.0 == exit.0 && cand.1 == exit.1 {
-                        advance_blizzards(blizzards, &bounds);
-                        return minute + 1;
-                    }
-
-                    if !next_to_visit.contains(&cand) {
-                        next_to_visit.push_back(cand);
-                    }
+                let idx = self.state_index(cand, time + 1);
+                if !seen[idx] {
+                    seen[idx] = true;
+                    to_visit.push_back((cand, time + 1));
                 }
             }
-
-            if !blizzards.iter().any(|b| b == &curr_pos) {
-                next_to_visit.push_back(curr_pos)
-            }
         }
-        assert!(!next_to_visit.is_empty());
-        to_visit.append(&mut next_to_visit);
+        None
     }
-}
-
-fn advance_blizzards(blizzards: &mut [Blizzard], bounds: &(i32, i32)) {
-    blizzards.iter_mut().for_each(|e| e.update_position(bounds));
-}
 
-fn find_exit(blizzards: &[Blizzard]) -> (i32, i32) {
-    let maxrow = blizzards.iter().map(|b| b.position.0).max().unwrap();
-    let maxcol = blizzards.iter().map(|b| b.position.1).max().unwrap();
-    (maxrow, maxcol)
+    /// Visits every waypoint in order, returns total time spent.
+    fn trip(&self, waypoints: &[(i32, i32)]) -> Option<usize> {
+        waypoints
+            .windows(2)
+            .try_fold(0, |time, leg| self.traverse(leg[0], leg[1], time))
+    }
 }
 
 fn main() {
-    let mut blizzards = Vec::new();
-    include_str!("../input")
-        .lines()
-        .enumerate()
-        .for_each(|(row, line)| {
-            line.chars().enumerate().for_each(|(col, char)| {
-                if ['^', 'v', '<', '>'].contains(&char) {
-                    let pos = ((row - 1) as i32, (col - 1) as i32);
-                    blizzards.push(Blizzard::with_direction(pos, &char));
-                }
-            });
-        });
-
-    // There are no optimizations applied to this BFS, so it's a bit slow.
-    // All 3 trips take about 3 seconds total in release mode.
-    let exit = find_exit(&blizzards);
-    let trip1 = traverse(&mut blizzards, (-1, 0), exit);
-    let trip2 = traverse(&mut blizzards, (exit.0 + 1, exit.1), (0, 0));
-    let trip3 = traverse(&mut blizzards, (-1, 0), exit);
+    let valley = Valley::from(include_str!("../input"));
+    let (entrance, exit) = (valley.entrance(), valley.exit());
+
+    let trip1 = valley.trip(&[entrance, exit]).unwrap();
+    let round_trip = valley.trip(&[entrance, exit, entrance, exit]).unwrap();
     println!("Part1: {}", trip1);
-    println!("Part2: {}", trip1 + trip2 + trip3);
+    println!("Part2: {}", round_trip);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotates_towards_lower_bits() {
+        assert_eq!(rotate_down(0b0001, 1, 4), 0b1000);
+        assert_eq!(rotate_down(0b0110, 1, 4), 0b0011);
+        assert_eq!(rotate_down(0b0110, 5, 4), 0b0011);
+        assert_eq!(rotate_down(0b0110, 4, 4), 0b0110);
+        assert_eq!(rotate_down(1, 1, 128), 1 << 127);
+    }
+
+    #[test]
+    fn blizzards_move_and_wrap() {
+        let valley = Valley::from("#.####\n#>..<#\n#v..^#\n#....#\n####.#");
+        assert_eq!((valley.height, valley.width, valley.period), (3, 4, 12));
+        assert_eq!(valley.occupied[0], vec![0b1001, 0b1001, 0]);
+        // Right and left swap places on row 0, down and up wrap around.
+        assert_eq!(valley.occupied[1], vec![0b1110, 0, 0b0001]);
+        assert_eq!(valley.occupied[2], vec![0b0111, 0, 0b1000]);
+        assert!(valley.is_free((1, 0), 1));
+        assert!(!valley.is_free((2, 0), 1));
+        assert!(!valley.is_free((0, 0), 12));
+    }
+
+    #[test]
+    fn example_trips() {
+        let valley = Valley::from(include_str!("../test-input"));
+        let (entrance, exit) = (valley.entrance(), valley.exit());
+        assert_eq!(valley.trip(&[entrance]), Some(0));
+        assert_eq!(valley.trip(&[entrance, exit]), Some(18));
+        assert_eq!(valley.trip(&[entrance, exit, entrance]), Some(18 + 23));
+        assert_eq!(valley.trip(&[entrance, exit, entrance, exit]), Some(54));
+    }
 }
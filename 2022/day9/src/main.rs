@@ -1,4 +1,3 @@
-use std::cmp::{max, min};
 use std::collections::HashSet;
 
 enum Directions {
@@ -32,108 +31,163 @@ impl Position {
     }
 }
 
-struct Knot {
-    position: Position,
-    visited: HashSet<Position>,
-    next: Option<Box<Knot>>,
+struct Rope {
+    knots: Vec<Position>,
+    visited: Vec<(usize, HashSet<Position>)>,
+    history: Option<Vec<Vec<Position>>>,
 }
 
-impl Knot {
-    fn new(next: Option<Box<Knot>>) -> Knot {
-        let position = Position::new(0, 0);
-        let visited = HashSet::new();
-        Knot {
-            position,
+impl Rope {
+    fn new(length: usize, tracked: &[usize]) -> Rope {
+        let knots = vec![Position::new(0, 0); length];
+        let visited = tracked
+            .iter()
+            .map(|idx| (*idx, HashSet::from([knots[*idx]])))
+            .collect();
+        Rope {
+            knots,
             visited,
-            next,
+            history: None,
         }
     }
 
-    fn move_in_direction(&mut self, direction: &Directions) {
+    /// Keeps a snapshot of every knot after each step, so they can be rendered.
+    fn with_history(mut self) -> Rope {
+        self.history = Some(vec![self.knots.clone()]);
+        self
+    }
+
+    fn move_head(&mut self, direction: &Directions) {
+        let head = &mut self.knots[0];
         match direction {
-            Directions::Left => self.position.x -= 1,
-            Directions::Right => self.position.x += 1,
-            Directions::Up => self.position.y += 1,
-            Directions::Down => self.position.y -= 1,
+            Directions::Left => head.x -= 1,
+            Directions::Right => head.x += 1,
+            Directions::Up => head.y += 1,
+            Directions::Down => head.y -= 1,
         };
 
-        self.visited.insert(self.position);
-        if let Some(next) = &mut self.next {
-            next.cascade_updates(&self.position);
+        for idx in 1..self.knots.len() {
+            let prev = self.knots[idx - 1];
+            let knot = &mut self.knots[idx];
+            let dx = prev.x - knot.x;
+            let dy = prev.y - knot.y;
+            if dx.abs() <= 1 && dy.abs() <= 1 {
+                // Once a knot stays put, so does the rest of the rope.
+                break;
+            }
+            knot.x += dx.clamp(-1, 1);
+            knot.y += dy.clamp(-1, 1);
         }
-    }
 
-    fn cascade_updates(&mut self, prev: &Position) {
-        let dx = prev.x - self.position.x;
-        let dy = prev.y - self.position.y;
-        if (dx.abs() > 1 && dy != 0) || (dy.abs() > 1 && dx != 0) {
-            self.position.x += min(max(dx, -1), 1);
-            self.position.y += min(max(dy, -1), 1);
-        } else {
-            self.position.x += dx / 2;
-            self.position.y += dy / 2;
+        for (idx, visited) in &mut self.visited {
+            visited.insert(self.knots[*idx]);
         }
-
-        self.visited.insert(self.position);
-        if let Some(next) = &mut self.next {
-            next.cascade_updates(&self.position);
+        if let Some(history) = &mut self.history {
+            history.push(self.knots.clone());
         }
     }
 
-    fn count_visited_by_tail(&self) -> usize {
-        if let Some(next) = &self.next {
-            return next.count_visited_by_tail();
-        }
-        self.visited.len()
+    fn count_visited_by(&self, n: usize) -> Option<usize> {
+        self.visited
+            .iter()
+            .find(|(idx, _)| *idx == n)
+            .map(|(_, visited)| visited.len())
     }
 
-    fn count_visited_by(&self, n: usize) -> usize {
-        if let Some(next) = &self.next {
-            if n > 0 {
-                return next.count_visited_by(n - 1);
-            }
-        }
-        self.visited.len()
+    fn trail(&self, n: usize) -> Option<Vec<Position>> {
+        let history = self.history.as_ref()?;
+        Some(history.iter().map(|knots| knots[n]).collect())
     }
-}
 
-struct Rope {
-    head: Knot,
-}
+    /// Draws the rope after a given step, the same way the puzzle text does.
+    fn render(&self, step: usize) -> Option<String> {
+        let history = self.history.as_ref()?;
+        let knots = history.get(step)?;
+
+        let all = history.iter().flatten();
+        let min_x = all.clone().map(|p| p.x).min()?;
+        let max_x = all.clone().map(|p| p.x).max()?;
+        let min_y = all.clone().map(|p| p.y).min()?;
+        let max_y = all.map(|p| p.y).max()?;
+
+        let label = |idx: usize| match idx {
+            0 => 'H',
+            idx if idx == knots.len() - 1 && knots.len() == 2 => 'T',
+            idx => char::from_digit(idx as u32, 10).unwrap_or('*'),
+        };
 
-impl Rope {
-    fn with_length(length: usize) -> Rope {
-        let mut knot = None;
-        for _ in 0..length {
-            knot = Some(Box::new(Knot::new(knot)));
-        }
-        Rope {
-            head: *knot.unwrap(),
+        let mut rendered = String::new();
+        for y in (min_y..=max_y).rev() {
+            for x in min_x..=max_x {
+                let pos = Position::new(x, y);
+                let chr = match knots.iter().position(|k| k == &pos) {
+                    Some(idx) => label(idx),
+                    None if x == 0 && y == 0 => 's',
+                    None => '.',
+                };
+                rendered.push(chr);
+            }
+            rendered.push('\n');
         }
-    }
-
-    fn move_head(&mut self, direction: &Directions) {
-        self.head.move_in_direction(direction);
-    }
-
-    fn count_visited_by_tail(&self) -> usize {
-        self.head.count_visited_by_tail()
-    }
-
-    fn count_visited_by(&self, n: usize) -> usize {
-        self.head.count_visited_by(n)
+        Some(rendered)
     }
 }
 
-fn main() {
-    let mut rope = Rope::with_length(10);
-    include_str!("../input").lines().for_each(|line| {
+fn simulate(rope: &mut Rope, moves: &str) {
+    moves.lines().for_each(|line| {
         let line = line.split_whitespace().collect::<Vec<&str>>().join("");
         let (direction, n_steps) = line.split_at(1);
         for _ in 0..n_steps.parse().unwrap() {
             rope.move_head(&Directions::from(direction));
         }
     });
-    println!("Part1: {}", rope.count_visited_by(1));
-    println!("Part2: {}", rope.count_visited_by_tail());
+}
+
+fn main() {
+    let render = std::env::args().any(|arg| arg == "--render");
+    let mut rope = Rope::new(10, &[1, 9]);
+    if render {
+        rope = rope.with_history();
+    }
+    simulate(&mut rope, include_str!("../input"));
+    println!("Part1: {}", rope.count_visited_by(1).unwrap());
+    println!("Part2: {}", rope.count_visited_by(9).unwrap());
+
+    if render {
+        let steps = rope.trail(9).map_or(0, |trail| trail.len() - 1);
+        if let Some(rendered) = rope.render(steps) {
+            println!("\n{rendered}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn example_visits() {
+        let mut rope = Rope::new(10, &[1, 9]);
+        simulate(&mut rope, include_str!("../test-input"));
+        assert_eq!(rope.count_visited_by(1), Some(13));
+        assert_eq!(rope.count_visited_by(9), Some(1));
+        assert_eq!(rope.count_visited_by(5), None);
+    }
+
+    #[test]
+    fn renders_like_the_puzzle() {
+        let mut rope = Rope::new(10, &[9]).with_history();
+        simulate(&mut rope, include_str!("../test-input-b"));
+        assert_eq!(rope.count_visited_by(9), Some(36));
+
+        let steps = rope.trail(9).unwrap().len() - 1;
+        let rendered = rope.render(steps).unwrap();
+        let rows = rendered.lines().collect::<Vec<&str>>();
+        assert_eq!(rows.len(), 21);
+        assert_eq!(rows[0], format!("H{}", ".".repeat(25)));
+        assert_eq!(rows[9], format!("9{}", ".".repeat(25)));
+        assert_eq!(rows[15], format!("{}s{}", ".".repeat(11), ".".repeat(14)));
+        assert_eq!(rope.render(steps + 1), None);
+        assert_eq!(Rope::new(2, &[1]).render(0), None);
+    }
 }
@@ -1,7 +1,9 @@
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{HashMap, HashSet};
 
 type Map = HashMap<(i32, i32), char>;
-type Loop = HashSet<(i32, i32)>;
+
+// North, east, south, west.
+const DIRECTIONS: [(i32, i32); 4] = [(-1, 0), (0, 1), (1, 0), (0, -1)];
 
 fn build_map(raw_map: &str) -> Map {
     let mut map = HashMap::new();
@@ -22,281 +24,185 @@ fn find_start(map: &Map) -> (i32, i32) {
     (0, 0)
 }
 
-fn steps_to_farthest(map: &Map, start: &(i32, i32)) -> (usize, Loop) {
-    let mut visited = HashSet::new();
-    visited.insert(*start);
-
-    let mut steps = HashMap::new();
-    steps.insert(*start, 0);
-
-    let mut queue = VecDeque::from_iter(get_valid_start_directions(map, start));
-
-    while let Some(pos) = queue.pop_front() {
-        let pipe = map.get(&pos).unwrap();
-        let neighbors = get_valid_neighbors(pipe, &pos);
-        for neighbor_pos in neighbors {
-            if visited.contains(&neighbor_pos) {
-                let neighbor_steps = steps.get(&neighbor_pos).unwrap();
-                steps.insert(pos, neighbor_steps + 1);
-            } else {
-                queue.push_back(neighbor_pos);
-            }
-            visited.insert(pos);
-        }
+/// Directions (indices into `DIRECTIONS`) a pipe connects to.
+fn connections(pipe: char) -> &'static [usize] {
+    match pipe {
+        '|' => &[0, 2],
+        '-' => &[1, 3],
+        'L' => &[0, 1],
+        'J' => &[0, 3],
+        '7' => &[2, 3],
+        'F' => &[1, 2],
+        _ => &[],
     }
-    (*steps.values().max().unwrap(), visited)
 }
 
-fn get_valid_start_directions(map: &Map, start: &(i32, i32)) -> Vec<(i32, i32)> {
-    let mut directions = Vec::new();
-    if let Some(east) = map.get(&(start.0, start.1 + 1)) {
-        if vec!['J', '7', '-'].contains(east) {
-            directions.push((start.0, start.1 + 1));
-        }
-    }
-    if let Some(west) = map.get(&(start.0, start.1 - 1)) {
-        if vec!['L', 'F', '-'].contains(west) {
-            directions.push((start.0, start.1 - 1));
-        }
-    }
-    if let Some(north) = map.get(&(start.0 - 1, start.1)) {
-        if vec!['7', 'F', '|'].contains(north) {
-            directions.push((start.0 - 1, start.1));
-        }
-    }
-    if let Some(south) = map.get(&(start.0 + 1, start.1)) {
-        if vec!['J', 'L', '|'].contains(south) {
-            directions.push((start.0 + 1, start.1));
-        }
-    }
-
-    assert!(directions.len() == 2);
-    directions
+fn pipe_with_connections(dirs: &[usize]) -> char {
+    ['|', '-', 'L', 'J', '7', 'F']
+        .into_iter()
+        .find(|pipe| connections(*pipe) == dirs)
+        .expect("S has to connect exactly two pipes")
 }
 
-fn get_valid_neighbors(pipe: &char, pos: &(i32, i32)) -> Vec<(i32, i32)> {
-    match pipe {
-        '|' => vec![(pos.0 + 1, pos.1), (pos.0 - 1, pos.1)],
-        '-' => vec![(pos.0, pos.1 + 1), (pos.0, pos.1 - 1)],
-        'L' => vec![(pos.0 - 1, pos.1), (pos.0, pos.1 + 1)],
-        'J' => vec![(pos.0 - 1, pos.1), (pos.0, pos.1 - 1)],
-        '7' => vec![(pos.0 + 1, pos.1), (pos.0, pos.1 - 1)],
-        'F' => vec![(pos.0 + 1, pos.1), (pos.0, pos.1 + 1)],
-        _ => unreachable!(),
-    }
+/// Infers what pipe is hiding under `S` from the neighbors that connect back to it.
+fn infer_start_pipe(map: &Map, start: &(i32, i32)) -> char {
+    let dirs = DIRECTIONS
+        .iter()
+        .enumerate()
+        .filter(|(dir, offset)| {
+            let neighbor = (start.0 + offset.0, start.1 + offset.1);
+            map.get(&neighbor)
+                .is_some_and(|pipe| connections(*pipe).contains(&((dir + 2) % 4)))
+        })
+        .map(|(dir, _)| dir)
+        .collect::<Vec<usize>>();
+    pipe_with_connections(&dirs)
 }
 
-fn remove_disconnected(map: &mut Map, loop_: &Loop) {
-    for (pos, elem) in map {
-        if !loop_.contains(pos) {
-            *elem = '.';
+/// Returns the loop in walking order, starting from `S`, and the pipe under `S`.
+fn trace_loop(map: &Map, start: &(i32, i32)) -> (Vec<(i32, i32)>, char) {
+    let start_pipe = infer_start_pipe(map, start);
+    let mut pipes = vec![*start];
+    let mut dir = connections(start_pipe)[0];
+    let mut pos = *start;
+
+    loop {
+        pos = (pos.0 + DIRECTIONS[dir].0, pos.1 + DIRECTIONS[dir].1);
+        if &pos == start {
+            break;
         }
-    }
-}
-
-enum BsideDirection {
-    Top,
-    Bottom,
-    Left,
-    Right,
-}
 
-fn update_bside(curr_pipe: &char, bside: BsideDirection) -> BsideDirection {
-    match curr_pipe {
-        '|' => bside,
-        '-' => bside,
-        'L' | '7' => match bside {
-            BsideDirection::Bottom => BsideDirection::Left,
-            BsideDirection::Top => BsideDirection::Right,
-            BsideDirection::Left => BsideDirection::Bottom,
-            BsideDirection::Right => BsideDirection::Top,
-        },
-        'J' | 'F' => match bside {
-            BsideDirection::Bottom => BsideDirection::Right,
-            BsideDirection::Top => BsideDirection::Left,
-            BsideDirection::Left => BsideDirection::Top,
-            BsideDirection::Right => BsideDirection::Bottom,
-        },
-        _ => unreachable!(),
+        pipes.push(pos);
+        let came_from = (dir + 2) % 4;
+        dir = *connections(map[&pos])
+            .iter()
+            .find(|d| **d != came_from)
+            .unwrap();
     }
-}
 
-fn mark_neighbors(
-    map: &Map,
-    marked: &mut Map,
-    curr_pos: &(i32, i32),
-    pipe: &char,
-    bside: &BsideDirection,
-) {
-    // Ugh.
-    match pipe {
-        '|' => match bside {
-            BsideDirection::Left => {
-                maybe_mark(map, marked, &(curr_pos.0, curr_pos.1 - 1), 'B');
-                maybe_mark(map, marked, &(curr_pos.0, curr_pos.1 + 1), 'A');
-            }
-            BsideDirection::Right => {
-                maybe_mark(map, marked, &(curr_pos.0, curr_pos.1 - 1), 'A');
-                maybe_mark(map, marked, &(curr_pos.0, curr_pos.1 + 1), 'B');
-            }
-            _ => unreachable!(),
-        },
-        '-' => match bside {
-            BsideDirection::Top => {
-                maybe_mark(map, marked, &(curr_pos.0 - 1, curr_pos.1), 'B');
-                maybe_mark(map, marked, &(curr_pos.0 + 1, curr_pos.1), 'A');
-            }
-            BsideDirection::Bottom => {
-                maybe_mark(map, marked, &(curr_pos.0 + 1, curr_pos.1), 'B');
-                maybe_mark(map, marked, &(curr_pos.0 - 1, curr_pos.1), 'A');
-            }
-            _ => unreachable!(),
-        },
-        'L' => match bside {
-            BsideDirection::Top | BsideDirection::Right => {
-                maybe_mark(map, marked, &(curr_pos.0 + 1, curr_pos.1), 'A');
-                maybe_mark(map, marked, &(curr_pos.0, curr_pos.1 - 1), 'A');
-            }
-            BsideDirection::Bottom | BsideDirection::Left => {
-                maybe_mark(map, marked, &(curr_pos.0 + 1, curr_pos.1), 'B');
-                maybe_mark(map, marked, &(curr_pos.0, curr_pos.1 - 1), 'B');
-            }
-        },
-        'J' => match bside {
-            BsideDirection::Bottom | BsideDirection::Right => {
-                maybe_mark(map, marked, &(curr_pos.0 + 1, curr_pos.1), 'B');
-                maybe_mark(map, marked, &(curr_pos.0, curr_pos.1 + 1), 'B');
-            }
-            BsideDirection::Top | BsideDirection::Left => {
-                maybe_mark(map, marked, &(curr_pos.0 + 1, curr_pos.1), 'A');
-                maybe_mark(map, marked, &(curr_pos.0, curr_pos.1 + 1), 'A');
-            }
-        },
-        '7' => match bside {
-            BsideDirection::Top | BsideDirection::Right => {
-                maybe_mark(map, marked, &(curr_pos.0 - 1, curr_pos.1), 'B');
-                maybe_mark(map, marked, &(curr_pos.0, curr_pos.1 + 1), 'B');
-            }
-            BsideDirection::Bottom | BsideDirection::Left => {
-                maybe_mark(map, marked, &(curr_pos.0 - 1, curr_pos.1), 'A');
-                maybe_mark(map, marked, &(curr_pos.0, curr_pos.1 + 1), 'A');
-            }
-        },
-        'F' => match bside {
-            BsideDirection::Top | BsideDirection::Left => {
-                maybe_mark(map, marked, &(curr_pos.0 - 1, curr_pos.1), 'B');
-                maybe_mark(map, marked, &(curr_pos.0, curr_pos.1 - 1), 'B');
-            }
-            BsideDirection::Bottom | BsideDirection::Right => {
-                maybe_mark(map, marked, &(curr_pos.0 - 1, curr_pos.1), 'A');
-                maybe_mark(map, marked, &(curr_pos.0, curr_pos.1 - 1), 'A');
-            }
-        },
-        _ => unreachable!(),
-    }
+    (pipes, start_pipe)
 }
 
-fn maybe_mark(map: &Map, marked: &mut Map, pos: &(i32, i32), value: char) {
-    if let Some('.') = map.get(pos) {
-        marked.insert(*pos, value);
-    }
+#[derive(Clone, Copy, PartialEq)]
+enum Tile {
+    Pipe(char),
+    Inside,
+    Outside,
 }
 
-fn follow_the_wall(map: &mut Map, start: &(i32, i32)) -> Map {
-    let mut visited = HashSet::new();
-    visited.insert(*start);
-
-    let mut marked = HashMap::new();
-    let mut bside = BsideDirection::Left;
+/// Even-odd scanline test. Walking a row left to right, every loop pipe that has
+/// a northern connection flips between outside and inside. Counting only northern
+/// connections deals with horizontal runs like `L--7` regardless of orientation.
+fn classify(map: &Map, pipes: &[(i32, i32)], start_pipe: char) -> Vec<Vec<Tile>> {
+    let on_loop = pipes.iter().copied().collect::<HashSet<(i32, i32)>>();
+    let maxrow = map.keys().map(|k| k.0).max().unwrap();
+    let maxcol = map.keys().map(|k| k.1).max().unwrap();
+
+    (0..=maxrow)
+        .map(|row| {
+            let mut inside = false;
+            (0..=maxcol)
+                .map(|col| {
+                    if !on_loop.contains(&(row, col)) {
+                        return if inside { Tile::Inside } else { Tile::Outside };
+                    }
 
-    let mut queue = VecDeque::from_iter(get_valid_start_directions(map, start));
-    // Only need to go in one direction.
-    queue.pop_front();
+                    let pipe = match map[&(row, col)] {
+                        'S' => start_pipe,
+                        pipe => pipe,
+                    };
+                    if connections(pipe).contains(&0) {
+                        inside = !inside;
+                    }
+                    Tile::Pipe(pipe)
+                })
+                .collect()
+        })
+        .collect()
+}
 
-    while let Some(pos) = queue.pop_front() {
-        let pipe = map.get(&pos).unwrap();
-        mark_neighbors(map, &mut marked, &pos, pipe, &bside);
-        bside = update_bside(pipe, bside);
-        let neighbors = get_valid_neighbors(pipe, &pos);
-        for neighbor_pos in neighbors {
-            if !visited.contains(&neighbor_pos) {
-                queue.push_back(neighbor_pos);
-                visited.insert(pos);
+fn render(tiles: &[Vec<Tile>]) -> String {
+    let mut rendered = String::new();
+    for row in tiles {
+        for tile in row {
+            match tile {
+                Tile::Pipe(pipe) => rendered.push(match pipe {
+                    '|' => '│',
+                    '-' => '─',
+                    'L' => '└',
+                    'J' => '┘',
+                    '7' => '┐',
+                    'F' => '┌',
+                    _ => unreachable!(),
+                }),
+                Tile::Inside => rendered.push_str("\x1b[1;32mI\x1b[0m"),
+                Tile::Outside => rendered.push_str("\x1b[2mO\x1b[0m"),
             }
         }
+        rendered.push('\n');
     }
-
-    marked
+    rendered
 }
 
-fn flood(map: &Map, marked: &Map, sign: char) -> Option<HashSet<(i32, i32)>> {
-    let targets = marked
-        .iter()
-        .filter(|elem| elem.1 == &sign)
-        .map(|elem| *elem.0)
-        .collect::<Vec<(i32, i32)>>();
-    let mut queue = VecDeque::from_iter(targets);
-    let mut visited = HashSet::new();
+fn main() {
+    let raw_map = include_str!("../input");
+    let map = build_map(raw_map);
+    let start = find_start(&map);
 
-    while let Some(pos) = queue.pop_front() {
-        let val = map.get(&pos);
-        val?;
+    let (pipes, start_pipe) = trace_loop(&map, &start);
+    println!("Part 1: {}", pipes.len() / 2);
 
-        if let Some('.') = val {
-            visited.insert(pos);
-            for offset_row in -1..1 {
-                for offset_col in -1..1 {
-                    let neighbor = (pos.0 + offset_row, pos.1 + offset_col);
-                    if !visited.contains(&neighbor) {
-                        queue.push_back(neighbor);
-                    }
-                }
-            }
-        }
+    let tiles = classify(&map, &pipes, start_pipe);
+    let part_2 = tiles
+        .iter()
+        .flatten()
+        .filter(|t| t == &&Tile::Inside)
+        .count();
+    println!("Part 2: {part_2}");
+
+    if std::env::args().any(|arg| arg == "--render") {
+        println!();
+        print!("{}", render(&tiles));
     }
-    Some(visited)
 }
 
-fn main() {
-    let raw_map = include_str!("../input");
-    let mut map = build_map(raw_map);
-    let start = find_start(&map);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    // Part 1 is straight forward. Just BFS in valid directions (connected pipes).
-    let (part_1, loop_) = steps_to_farthest(&map, &start);
-    println!("Part 1: {part_1}");
+    fn solve(raw_map: &str) -> (usize, Vec<Vec<Tile>>) {
+        let map = build_map(raw_map);
+        let (pipes, start_pipe) = trace_loop(&map, &find_start(&map));
+        (pipes.len() / 2, classify(&map, &pipes, start_pipe))
+    }
 
-    // For part 2 the strat is to follow the wall of pipe loop, consistently marking the sides.
-    // Starting from S and picking the left hand side to be represented as "A" in:
-    // ..........
-    // .S------7.
-    // .|F----7|.
-    // .||....||.
-    // .||....||.
-    // .|L-7F-J|.
-    // .|..||..|.
-    // .L--JL--J.
-    // ..........
-    //
-    // Results in:
-    // AAAAAAAAAA
-    // AS------7A
-    // A|F----7|A
-    // A||BBBB||A
-    // A||BBBB||A
-    // A|L-7F-J|A
-    // A|BB||BB|A
-    // AL--JL--JA
-    // AAAAAAAAAA
-    //
-    // For the real input, this will leave a bunch of spots that were not visited, so we can just flood fill
-    // with both "A" and "B". One flood fill will go beyond map bounds and can be discarded, as it represents
-    // regions outside of the loop. Total area of the remaining region is the answer to part 2. :^)
-    remove_disconnected(&mut map, &loop_);
-    let marked = follow_the_wall(&mut map, &start);
-    let flooded = flood(&map, &marked, 'B');
+    fn inside(tiles: &[Vec<Tile>]) -> usize {
+        tiles
+            .iter()
+            .flatten()
+            .filter(|t| t == &&Tile::Inside)
+            .count()
+    }
+
+    #[test]
+    fn examples() {
+        assert_eq!(solve(include_str!("../test-input")).0, 4);
+        assert_eq!(solve(include_str!("../test-input2")).0, 8);
+        assert_eq!(inside(&solve(include_str!("../test-input3")).1), 4);
+        assert_eq!(inside(&solve(include_str!("../test-input4")).1), 8);
+        assert_eq!(inside(&solve(include_str!("../test-input5")).1), 10);
+    }
 
-    if let Some(filled) = flooded {
-        println!("Part 2: {}", filled.len());
+    #[test]
+    fn renders_pipes_and_tiles() {
+        let rendered = render(&solve(include_str!("../test-input")).1);
+        let rows = rendered.lines().collect::<Vec<&str>>();
+        let (inside, outside) = ("\x1b[1;32mI\x1b[0m", "\x1b[2mO\x1b[0m");
+        assert_eq!(rows[1], format!("{outside}┌─┐{outside}"));
+        assert_eq!(rows[2], format!("{outside}│{inside}│{outside}"));
+        assert_eq!(rows[3], format!("{outside}└─┘{outside}"));
+
+        let rendered = render(&solve(include_str!("../test-input4")).1);
+        assert_eq!(rendered.matches("\x1b[1;32mI").count(), 8);
     }
 }
@@ -1,80 +1,256 @@
-use std::collections::HashMap;
+use std::fmt;
 
-struct Springs(HashMap<(usize, usize, usize), usize>);
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Cell {
+    Empty,
+    Filled,
+    Unknown,
+}
 
-impl Springs {
-    fn new() -> Self {
-        Self(HashMap::new())
+impl From<char> for Cell {
+    fn from(value: char) -> Self {
+        match value {
+            '.' => Cell::Empty,
+            '#' => Cell::Filled,
+            '?' => Cell::Unknown,
+            _ => panic!("Unknown cell {value}"),
+        }
     }
+}
 
-    fn count_arrangements(&mut self, config: &str, groups: &[usize]) -> usize {
-        self.arrange(config, groups, 0, 0, 0)
+impl fmt::Display for Cell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cell::Empty => write!(f, "."),
+            Cell::Filled => write!(f, "#"),
+            Cell::Unknown => write!(f, "?"),
+        }
     }
+}
 
-    fn arrange(
-        &mut self,
-        config: &str,
-        groups: &[usize],
-        cidx: usize,
-        gidx: usize,
-        group_len: usize,
-    ) -> usize {
-        if let Some(partial) = self.0.get(&(cidx, gidx, group_len)) {
-            return *partial;
+struct LineCounter {
+    pattern: Vec<Cell>,
+    groups: Vec<usize>,
+    // ways[i][g] is the number of arrangements of pattern[i..] using groups[g..].
+    ways: Vec<Vec<usize>>,
+}
+
+impl LineCounter {
+    fn new(pattern: &[Cell], groups: &[usize]) -> Self {
+        let n = pattern.len();
+        let mut empties = vec![0; n + 1];
+        for (idx, cell) in pattern.iter().enumerate() {
+            empties[idx + 1] = empties[idx] + usize::from(*cell == Cell::Empty);
         }
 
-        if cidx == config.len() {
-            if gidx == groups.len() - 1 && (group_len == groups[gidx] || group_len == 0) {
-                return 1;
+        let mut ways = vec![vec![0; groups.len() + 1]; n + 1];
+        ways[n][groups.len()] = 1;
+        for idx in (0..n).rev() {
+            for gidx in 0..=groups.len() {
+                let mut partial = 0;
+                if pattern[idx] != Cell::Filled {
+                    partial += ways[idx + 1][gidx];
+                }
+
+                if gidx < groups.len() {
+                    let end = idx + groups[gidx];
+                    if end <= n
+                        && empties[end] == empties[idx]
+                        && (end == n || pattern[end] != Cell::Filled)
+                    {
+                        partial += ways[(end + 1).min(n)][gidx + 1];
+                    }
+                }
+
+                ways[idx][gidx] = partial;
             }
-            return 0;
         }
 
-        if gidx > groups.len() - 1 || groups[gidx] < group_len {
-            return 0;
+        Self {
+            pattern: pattern.to_vec(),
+            groups: groups.to_vec(),
+            ways,
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.ways[0][0]
+    }
+
+    /// Returns `index`-th arrangement, ordered as if `.` sorted before `#`.
+    fn arrangement(&self, mut index: usize) -> Option<Vec<Cell>> {
+        if index >= self.count() {
+            return None;
         }
 
-        let current_char = config.chars().nth(cidx).unwrap();
-        let mut partial = 0;
+        let n = self.pattern.len();
+        let mut line = Vec::with_capacity(n);
+        let (mut idx, mut gidx) = (0, 0);
+        while idx < n {
+            let skip = if self.pattern[idx] != Cell::Filled {
+                self.ways[idx + 1][gidx]
+            } else {
+                0
+            };
+
+            if index < skip {
+                line.push(Cell::Empty);
+                idx += 1;
+                continue;
+            }
+
+            index -= skip;
+            let end = idx + self.groups[gidx];
+            line.extend(vec![Cell::Filled; self.groups[gidx]]);
+            if end < n {
+                line.push(Cell::Empty);
+            }
+            idx = (end + 1).min(n);
+            gidx += 1;
+        }
+
+        Some(line)
+    }
+
+    fn arrangements(&self) -> impl Iterator<Item = Vec<Cell>> + '_ {
+        (0..self.count()).filter_map(|index| self.arrangement(index))
+    }
+
+    /// Picks one arrangement uniformly at random, the same one for the same seed.
+    fn sample(&self, seed: u64) -> Option<Vec<Cell>> {
+        if self.count() == 0 {
+            return None;
+        }
+
+        // splitmix64 outputs, rejecting the top ones that would bias the modulo.
+        let count = self.count() as u64;
+        let limit = u64::MAX - u64::MAX % count;
+        let mut state = seed;
+        loop {
+            state = state.wrapping_add(0x9e3779b97f4a7c15);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            z ^= z >> 31;
+            if z < limit {
+                return self.arrangement((z % count) as usize);
+            }
+        }
+    }
+
+    /// Cells that have the same value in every arrangement, `Unknown` otherwise.
+    fn forced_cells(&self) -> Option<Vec<Cell>> {
+        if self.count() == 0 {
+            return None;
+        }
+
+        let mut line = self.pattern.clone();
+        for idx in 0..line.len() {
+            if line[idx] != Cell::Unknown {
+                continue;
+            }
+
+            let mut probe = line.clone();
+            probe[idx] = Cell::Filled;
+            if LineCounter::new(&probe, &self.groups).count() == 0 {
+                line[idx] = Cell::Empty;
+                continue;
+            }
 
-        if (current_char == '.' || current_char == '?')
-            && (group_len == 0 || group_len == groups[gidx])
-        {
-            partial += self.arrange(config, groups, cidx + 1, gidx, 0);
+            probe[idx] = Cell::Empty;
+            if LineCounter::new(&probe, &self.groups).count() == 0 {
+                line[idx] = Cell::Filled;
+            }
         }
 
-        if current_char == '#' || current_char == '?' {
-            let mut new_gidx = gidx;
-            if group_len == 0 {
-                new_gidx += 1;
+        Some(line)
+    }
+}
+
+struct Nonogram {
+    rows: Vec<Vec<usize>>,
+    cols: Vec<Vec<usize>>,
+}
+
+impl Nonogram {
+    fn solve(&self) -> Option<Vec<Vec<Cell>>> {
+        let grid = vec![vec![Cell::Unknown; self.cols.len()]; self.rows.len()];
+        self.solve_from(grid)
+    }
+
+    fn solve_from(&self, mut grid: Vec<Vec<Cell>>) -> Option<Vec<Vec<Cell>>> {
+        // Propagate row and column constraints until nothing changes.
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (row, groups) in self.rows.iter().enumerate() {
+                let line = LineCounter::new(&grid[row], groups).forced_cells()?;
+                if line != grid[row] {
+                    grid[row] = line;
+                    changed = true;
+                }
+            }
+
+            for (col, groups) in self.cols.iter().enumerate() {
+                let column = grid.iter().map(|row| row[col]).collect::<Vec<Cell>>();
+                let line = LineCounter::new(&column, groups).forced_cells()?;
+                if line != column {
+                    for (row, cell) in line.into_iter().enumerate() {
+                        grid[row][col] = cell;
+                    }
+                    changed = true;
+                }
             }
-            partial += self.arrange(config, groups, cidx + 1, new_gidx, group_len + 1);
         }
 
-        // State representation inspired by jonathanpaulson.
-        // https://github.com/jonathanpaulson/AdventOfCode/blob/5ac1fc19f9eaa84e43ccad5e13778cd3a1919a92/2023/12.py#L16
-        self.0.insert((cidx, gidx, group_len), partial);
-        partial
+        // Propagation alone does not solve every puzzle, so guess and backtrack.
+        let unknown = grid.iter().enumerate().find_map(|(row, line)| {
+            line.iter()
+                .position(|c| c == &Cell::Unknown)
+                .map(|col| (row, col))
+        });
+
+        match unknown {
+            None => Some(grid),
+            Some((row, col)) => [Cell::Filled, Cell::Empty].iter().find_map(|guess| {
+                let mut next = grid.clone();
+                next[row][col] = *guess;
+                self.solve_from(next)
+            }),
+        }
     }
 }
 
-fn parse_springs(raw_spring: &str, repeat: usize) -> (String, Vec<usize>) {
+fn render_line(line: &[Cell]) -> String {
+    line.iter().map(|c| c.to_string()).collect()
+}
+
+fn parse_springs(raw_spring: &str, repeat: usize) -> (Vec<Cell>, Vec<usize>) {
     let mut records = raw_spring.split(' ');
-    let config = vec![records.next().unwrap(); repeat].join("?");
+    let config = vec![records.next().unwrap(); repeat]
+        .join("?")
+        .chars()
+        .map(Cell::from)
+        .collect();
 
-    let mut groups = records
+    let groups = records
         .next()
         .unwrap()
         .split(',')
         .filter_map(|num| num.parse().ok())
-        .collect::<Vec<usize>>();
-
-    groups = groups.repeat(repeat);
-    groups.insert(0, 0);
+        .collect::<Vec<usize>>()
+        .repeat(repeat);
 
     (config, groups)
 }
 
+/// Clues of one axis of a nonogram, lines separated by `,` and groups by spaces.
+fn parse_clues(raw: &str) -> Vec<Vec<usize>> {
+    raw.split(',')
+        .map(|line| line.split(' ').map(|num| num.parse().unwrap()).collect())
+        .collect()
+}
+
 fn main() {
     let raw_springs = include_str!("../input").lines().collect::<Vec<&str>>();
 
@@ -82,8 +258,7 @@ fn main() {
         .iter()
         .map(|raw_spring| {
             let (config, groups) = parse_springs(raw_spring, 1);
-            let mut springs = Springs::new();
-            springs.count_arrangements(&config, &groups)
+            LineCounter::new(&config, &groups).count()
         })
         .sum::<usize>();
     println!("Part 1: {part1}");
@@ -92,9 +267,116 @@ fn main() {
         .iter()
         .map(|raw_spring| {
             let (config, groups) = parse_springs(raw_spring, 5);
-            let mut springs = Springs::new();
-            springs.count_arrangements(&config, &groups)
+            LineCounter::new(&config, &groups).count()
         })
         .sum::<usize>();
     println!("Part 2: {part2}");
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            // `--arrangements "???.### 1,1,3"` lists a record's arrangements.
+            "--arrangements" => {
+                let record = args.next().expect("Missing record");
+                let (config, groups) = parse_springs(&record, 1);
+                let counter = LineCounter::new(&config, &groups);
+                println!("\n{}", render_line(&config));
+                for arrangement in counter.arrangements() {
+                    println!("{}", render_line(&arrangement));
+                }
+                if let Some(sample) = counter.sample(2023) {
+                    println!("Sampled: {}", render_line(&sample));
+                }
+            }
+            // `--nonogram "1 1,5,5,3,1" "2,4,4,4,2"` solves a picture from its
+            // row and column clues.
+            "--nonogram" => {
+                let mut clues = || parse_clues(&args.next().expect("Missing clues"));
+                let nonogram = Nonogram {
+                    rows: clues(),
+                    cols: clues(),
+                };
+                println!();
+                match nonogram.solve() {
+                    Some(solution) => {
+                        for row in solution {
+                            println!("{}", render_line(&row));
+                        }
+                    }
+                    None => println!("No solution"),
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = include_str!("../test-input");
+
+    #[test]
+    fn example_counts() {
+        let counts = |repeat| {
+            EXAMPLE
+                .lines()
+                .map(|line| {
+                    let (config, groups) = parse_springs(line, repeat);
+                    LineCounter::new(&config, &groups).count()
+                })
+                .collect::<Vec<usize>>()
+        };
+        assert_eq!(counts(1), vec![1, 4, 1, 1, 4, 10]);
+        assert_eq!(counts(5), vec![1, 16384, 1, 16, 2500, 506250]);
+    }
+
+    #[test]
+    fn lists_every_arrangement() {
+        let (config, groups) = parse_springs("?###???????? 3,2,1", 1);
+        let counter = LineCounter::new(&config, &groups);
+        let arrangements = counter
+            .arrangements()
+            .map(|a| render_line(&a))
+            .collect::<Vec<String>>();
+        assert_eq!(arrangements.len(), 10);
+        assert_eq!(arrangements[0], ".###....##.#");
+        assert_eq!(arrangements[9], ".###.##.#...");
+    }
+
+    #[test]
+    fn samples_every_arrangement() {
+        let (config, groups) = parse_springs(".??..??...?##. 1,1,3", 1);
+        let counter = LineCounter::new(&config, &groups);
+        let mut seen = vec![0; counter.count()];
+        for seed in 0..4000 {
+            let sample = counter.sample(seed).unwrap();
+            let index = counter.arrangements().position(|a| a == sample).unwrap();
+            seen[index] += 1;
+        }
+        assert!(
+            seen.iter().all(|times| (800..1200).contains(times)),
+            "{seen:?}"
+        );
+        assert_eq!(counter.sample(7), counter.sample(7));
+
+        let (config, groups) = parse_springs("### 1", 1);
+        assert_eq!(LineCounter::new(&config, &groups).sample(0), None);
+    }
+
+    #[test]
+    fn solves_nonogram() {
+        let nonogram = Nonogram {
+            rows: parse_clues("1 1,5,5,3,1"),
+            cols: parse_clues("2,4,4,4,2"),
+        };
+        let picture = nonogram
+            .solve()
+            .unwrap()
+            .iter()
+            .map(|row| render_line(row))
+            .collect::<Vec<String>>();
+        assert_eq!(picture, vec![".#.#.", "#####", "#####", ".###.", "..#.."]);
+    }
 }
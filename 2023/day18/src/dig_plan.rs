@@ -0,0 +1,43 @@
+use crate::polygon::Polygon;
+
+/// Traces a dig plan of `(direction, distance)` steps, where direction is an
+/// index into `directions` given as `(row, col)` offsets.
+pub fn trace_polygon(plan: &[(usize, i64)], directions: &[(i64, i64)]) -> Polygon {
+    let mut vertices = Vec::with_capacity(plan.len());
+    let mut curr = (0, 0);
+    for (direction, distance) in plan {
+        vertices.push(curr);
+        curr = (
+            curr.0 + directions[*direction].0 * distance,
+            curr.1 + directions[*direction].1 * distance,
+        );
+    }
+    assert_eq!(curr, (0, 0), "Dig plan does not close the loop");
+    Polygon::new(vertices)
+}
+
+/// Draws the trench as `#` on a `.` background. Only meant for small plans.
+pub fn render(polygon: &Polygon) -> String {
+    let vertices = polygon.vertices();
+    let min_row = vertices.iter().map(|v| v.0).min().unwrap_or(0);
+    let max_row = vertices.iter().map(|v| v.0).max().unwrap_or(0);
+    let min_col = vertices.iter().map(|v| v.1).min().unwrap_or(0);
+    let max_col = vertices.iter().map(|v| v.1).max().unwrap_or(0);
+
+    let width = (max_col - min_col + 1) as usize;
+    let height = (max_row - min_row + 1) as usize;
+    let mut canvas = vec![vec!['.'; width]; height];
+    for (a, b) in polygon.edges() {
+        for row in a.0.min(b.0)..=a.0.max(b.0) {
+            for col in a.1.min(b.1)..=a.1.max(b.1) {
+                canvas[(row - min_row) as usize][(col - min_col) as usize] = '#';
+            }
+        }
+    }
+
+    canvas
+        .into_iter()
+        .map(|row| row.into_iter().collect::<String>())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
@@ -1,7 +1,9 @@
-use geo::{Area, LineString, Polygon};
+mod dig_plan;
+#[path = "../../../common/polygon.rs"]
+mod polygon;
 
 // Up, right, down, left.
-const DIRECTIONS: &[(f64, f64)] = &[(-1., 0.), (0., 1.), (1., 0.), (0., -1.)];
+const DIRECTIONS: &[(i64, i64)] = &[(-1, 0), (0, 1), (1, 0), (0, -1)];
 
 fn get_directions(raw_directions: &str) -> usize {
     match raw_directions {
@@ -17,52 +19,33 @@ fn rotate_directions(raw_directions: usize) -> usize {
     (raw_directions + 1) % 4
 }
 
-fn parse_small_plan(raw_plan: &str) -> Vec<(usize, f64)> {
+fn parse_small_plan(raw_plan: &str) -> Vec<(usize, i64)> {
     raw_plan
         .lines()
         .map(|line| {
             let mut plan = line.split(' ');
             let direction = get_directions(plan.next().unwrap());
-            let distance = plan.next().unwrap().parse::<f64>().unwrap();
+            let distance = plan.next().unwrap().parse::<i64>().unwrap();
             (direction, distance)
         })
-        .collect::<Vec<(usize, f64)>>()
+        .collect::<Vec<(usize, i64)>>()
 }
 
-fn parse_big_plan(raw_plan: &str) -> Vec<(usize, f64)> {
+fn parse_big_plan(raw_plan: &str) -> Vec<(usize, i64)> {
     raw_plan
         .lines()
         .map(|line| {
-            let plan = line.split(' ');
-            let mut hex = plan.last().unwrap().replace(['(', ')', '#'], "");
+            let mut plan = line.split(' ');
+            let mut hex = plan.next_back().unwrap().replace(['(', ')', '#'], "");
             let direction = rotate_directions(hex.pop().unwrap().to_digit(10).unwrap() as usize);
-            let distance = i64::from_str_radix(&hex, 16).unwrap() as f64;
+            let distance = i64::from_str_radix(&hex, 16).unwrap();
             (direction, distance)
         })
-        .collect::<Vec<(usize, f64)>>()
+        .collect::<Vec<(usize, i64)>>()
 }
 
-fn calculate_lagoon_area(vertices: Vec<(f64, f64)>) -> usize {
-    let polygon = Polygon::new(LineString::from(vertices), vec![]);
-    polygon.unsigned_area() as usize
-}
-
-fn calculate_lagoon_capacity(plan: Vec<(usize, f64)>) -> usize {
-    let mut buff = Vec::new();
-    let mut curr = (0., 0.);
-    let mut perimeter = 0;
-
-    for (direction, distance) in plan {
-        perimeter += distance as usize;
-        buff.push((curr.0, curr.1));
-
-        curr = (
-            curr.0 + (DIRECTIONS[direction].0 * distance),
-            curr.1 + (DIRECTIONS[direction].1 * distance),
-        );
-    }
-
-    calculate_lagoon_area(buff) + (perimeter / 2) + 1
+fn calculate_lagoon_capacity(plan: Vec<(usize, i64)>) -> i128 {
+    dig_plan::trace_polygon(&plan, DIRECTIONS).lattice_points()
 }
 
 fn main() {
@@ -77,4 +60,32 @@ fn main() {
         "Part 2: {}",
         calculate_lagoon_capacity(parse_big_plan(raw_plan))
     );
+
+    if std::env::args().any(|arg| arg == "--render") {
+        let lagoon = dig_plan::trace_polygon(&parse_small_plan(raw_plan), DIRECTIONS);
+        println!("\n{}", dig_plan::render(&lagoon));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = include_str!("../test-input");
+
+    #[test]
+    fn example_capacity() {
+        assert_eq!(calculate_lagoon_capacity(parse_small_plan(EXAMPLE)), 62);
+        assert_eq!(
+            calculate_lagoon_capacity(parse_big_plan(EXAMPLE)),
+            952408144115
+        );
+    }
+
+    #[test]
+    fn example_render() {
+        let example = dig_plan::trace_polygon(&parse_small_plan(EXAMPLE), DIRECTIONS);
+        let rendered = dig_plan::render(&example);
+        assert!(rendered.starts_with("#######\n#.....#\n###...#"));
+    }
 }
@@ -1,10 +1,19 @@
 use std::collections::HashMap;
+use std::fmt;
 
 trait Rated {
     fn rate(&self, part: &Part) -> Status;
-    fn count_accepted(&self, part_range: PartRange) -> usize;
+    /// Collects the ranges of parts that end up accepted.
+    fn accepted_ranges(&self, part_range: PartRange, accepted: &mut Vec<PartRange>);
+    /// Drops every rule that can't make a difference for parts in `part_range`.
+    fn prune(self: Box<Self>, part_range: PartRange) -> Box<dyn Rated>;
+    fn status(&self) -> Option<Status>;
+    fn label(&self) -> String;
+    /// Outcomes when the rule passes and fails, in that order.
+    fn outcomes(&self) -> Vec<&dyn Rated>;
 }
 
+#[derive(Clone, Copy)]
 enum Category {
     X,
     M,
@@ -35,6 +44,14 @@ impl From<&Category> for usize {
     }
 }
 
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let idx: usize = self.into();
+        write!(f, "{}", &"xmas"[idx..=idx])
+    }
+}
+
+#[derive(Clone, Copy)]
 enum Condition {
     Greater,
     Less,
@@ -50,7 +67,16 @@ impl From<char> for Condition {
     }
 }
 
-#[derive(Clone, Copy)]
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Greater => write!(f, ">"),
+            Self::Less => write!(f, "<"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
 enum Status {
     Accepted,
     Rejected,
@@ -61,12 +87,30 @@ impl Rated for Status {
         *self
     }
 
-    fn count_accepted(&self, part_range: PartRange) -> usize {
+    fn accepted_ranges(&self, part_range: PartRange, accepted: &mut Vec<PartRange>) {
+        if self.is_accepted() {
+            accepted.push(part_range);
+        }
+    }
+
+    fn prune(self: Box<Self>, _: PartRange) -> Box<dyn Rated> {
+        self
+    }
+
+    fn status(&self) -> Option<Status> {
+        Some(*self)
+    }
+
+    fn label(&self) -> String {
         match self {
-            Self::Rejected => 0,
-            Self::Accepted => part_range.0.iter().map(|rng| rng.1 - rng.0 + 1).product(),
+            Self::Accepted => "A".to_string(),
+            Self::Rejected => "R".to_string(),
         }
     }
+
+    fn outcomes(&self) -> Vec<&dyn Rated> {
+        Vec::new()
+    }
 }
 
 impl Status {
@@ -101,6 +145,33 @@ impl Rule {
             on_fail,
         }
     }
+
+    /// Splits a range into the parts that pass the rule and the ones that fail it.
+    fn split(&self, part_range: PartRange) -> (Option<PartRange>, Option<PartRange>) {
+        let mut passing = part_range.clone();
+        let mut failing = part_range.clone();
+        let rng = part_range.get_range(&self.category);
+
+        match self.condition {
+            Condition::Greater => {
+                passing.set_range(rng.0.max(self.value + 1), rng.1, &self.category);
+                failing.set_range(rng.0, rng.1.min(self.value), &self.category);
+            }
+            Condition::Less => {
+                // Nothing is rated below zero, so with `<0` nothing passes.
+                match self.value.checked_sub(1) {
+                    Some(below) => passing.set_range(rng.0, rng.1.min(below), &self.category),
+                    None => passing.set_range(1, 0, &self.category),
+                }
+                failing.set_range(rng.0.max(self.value), rng.1, &self.category);
+            }
+        };
+
+        (
+            Some(passing).filter(|r| !r.is_empty()),
+            Some(failing).filter(|r| !r.is_empty()),
+        )
+    }
 }
 
 impl Rated for Rule {
@@ -116,79 +187,158 @@ impl Rated for Rule {
         self.on_fail.rate(part)
     }
 
-    fn count_accepted(&self, part_range: PartRange) -> usize {
-        let mut passing = part_range.clone();
-        let mut failing = part_range.clone();
-        let rng = part_range.get_range(&self.category);
+    fn accepted_ranges(&self, part_range: PartRange, accepted: &mut Vec<PartRange>) {
+        let (passing, failing) = self.split(part_range);
+        if let Some(passing) = passing {
+            self.on_pass.accepted_ranges(passing, accepted);
+        }
+        if let Some(failing) = failing {
+            self.on_fail.accepted_ranges(failing, accepted);
+        }
+    }
 
-        match self.condition {
-            Condition::Greater => {
-                passing.set_range(self.value + 1, rng.1, &self.category);
-                failing.set_range(rng.0, self.value, &self.category);
-            }
-            Condition::Less => {
-                passing.set_range(rng.0, self.value - 1, &self.category);
-                failing.set_range(self.value, rng.1, &self.category);
+    fn prune(self: Box<Self>, part_range: PartRange) -> Box<dyn Rated> {
+        let rule = *self;
+        match rule.split(part_range) {
+            (Some(passing), None) => rule.on_pass.prune(passing),
+            (None, Some(failing)) => rule.on_fail.prune(failing),
+            (None, None) => Box::new(Status::Rejected),
+            (Some(passing), Some(failing)) => {
+                let on_pass = rule.on_pass.prune(passing);
+                let on_fail = rule.on_fail.prune(failing);
+                match (on_pass.status(), on_fail.status()) {
+                    (Some(pass), Some(fail)) if pass == fail => on_pass,
+                    _ => Box::new(Rule {
+                        on_pass,
+                        on_fail,
+                        ..rule
+                    }),
+                }
             }
-        };
+        }
+    }
+
+    fn status(&self) -> Option<Status> {
+        None
+    }
+
+    fn label(&self) -> String {
+        format!("{}{}{}", self.category, self.condition, self.value)
+    }
 
-        self.on_pass.count_accepted(passing) + self.on_fail.count_accepted(failing)
+    fn outcomes(&self) -> Vec<&dyn Rated> {
+        vec![self.on_pass.as_ref(), self.on_fail.as_ref()]
     }
 }
 
-fn build_workflows_lookup(raw_workflows: &str) -> HashMap<String, String> {
-    let mut workflows_lut = HashMap::new();
-    for line in raw_workflows.lines() {
-        let workflow_and_rules = line.split('{').collect::<Vec<&str>>();
-
-        let workflow_name = workflow_and_rules[0];
-        let raw_rules = workflow_and_rules[1];
-
-        for (idx, rule) in raw_rules[..raw_rules.len() - 1].split(',').enumerate() {
-            let flat_worfklow_name = format!("{workflow_name}{idx}");
-            if rule.contains('>') || rule.contains('<') {
-                let next = idx + 1;
-                if rule.ends_with('A') || rule.ends_with('R') {
-                    workflows_lut
-                        .insert(flat_worfklow_name, format!("{rule}:{workflow_name}{next}"));
-                } else {
-                    workflows_lut
-                        .insert(flat_worfklow_name, format!("{rule}0:{workflow_name}{next}"));
-                }
-            } else if rule.ends_with('A') || rule.ends_with('R') {
-                workflows_lut.insert(flat_worfklow_name, rule.to_string());
-            } else {
-                workflows_lut.insert(flat_worfklow_name, format!("{rule}0"));
+fn render_tree(tree: &dyn Rated) -> String {
+    fn render_outcomes(node: &dyn Rated, prefix: &str, rendered: &mut String) {
+        let outcomes = node.outcomes();
+        for (idx, outcome) in outcomes.iter().enumerate() {
+            let last = idx == outcomes.len() - 1;
+            let branch = if last { "└─" } else { "├─" };
+            let verdict = if idx == 0 { "pass" } else { "fail" };
+            rendered.push_str(&format!(
+                "{prefix}{branch} {verdict}: {}\n",
+                outcome.label()
+            ));
+
+            let indent = if last { "   " } else { "│  " };
+            render_outcomes(*outcome, &format!("{prefix}{indent}"), rendered);
+        }
+    }
+
+    let mut rendered = format!("{}\n", tree.label());
+    render_outcomes(tree, "", &mut rendered);
+    rendered
+}
+
+#[derive(Debug)]
+enum WorkflowError {
+    /// The workflow every part starts in isn't there.
+    MissingEntry(String),
+    Undefined {
+        workflow: String,
+        referenced_by: String,
+    },
+    Cycle(Vec<String>),
+}
+
+impl fmt::Display for WorkflowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingEntry(workflow) => {
+                write!(f, "No workflow {workflow} for parts to start in")
             }
+            Self::Undefined {
+                workflow,
+                referenced_by,
+            } => write!(
+                f,
+                "Workflow {referenced_by} sends parts to undefined workflow {workflow}"
+            ),
+            Self::Cycle(workflows) => write!(f, "Workflows loop: {}", workflows.join(" -> ")),
         }
     }
+}
 
+fn build_workflows_lookup(raw_workflows: &str) -> HashMap<&str, Vec<&str>> {
+    let mut workflows_lut = HashMap::new();
+    for line in raw_workflows.lines() {
+        let (workflow_name, raw_rules) = line.split_once('{').unwrap();
+        let rules = raw_rules.trim_end_matches('}').split(',').collect();
+        workflows_lut.insert(workflow_name, rules);
+    }
     workflows_lut
 }
 
-fn add_rule(name: &str, workflows_lut: &HashMap<String, String>) -> Box<dyn Rated> {
+/// Compiles a workflow, and everything it sends parts to, into a single tree.
+/// `path` holds the workflows currently being compiled, to catch loops.
+fn compile_workflow<'a>(
+    name: &'a str,
+    workflows_lut: &HashMap<&'a str, Vec<&'a str>>,
+    path: &mut Vec<&'a str>,
+) -> Result<Box<dyn Rated>, WorkflowError> {
     match name {
-        "A" => Box::new(Status::Accepted),
-        "R" => Box::new(Status::Rejected),
-        _ => {
-            let entry = workflows_lut.get(name).unwrap();
-            let parts = entry.split(':').collect::<Vec<&str>>();
-
-            if parts.len() == 1 {
-                return add_rule(parts[0], workflows_lut);
-            }
+        "A" => return Ok(Box::new(Status::Accepted)),
+        "R" => return Ok(Box::new(Status::Rejected)),
+        _ => {}
+    }
 
-            let raw_rule = parts[0];
-            let on_pass = add_rule(parts[1], workflows_lut);
-            let on_fail = add_rule(parts[2], workflows_lut);
+    if let Some(start) = path.iter().position(|w| *w == name) {
+        let mut cycle = path[start..]
+            .iter()
+            .map(|w| w.to_string())
+            .collect::<Vec<String>>();
+        cycle.push(name.to_string());
+        return Err(WorkflowError::Cycle(cycle));
+    }
 
-            Box::new(Rule::new_with_outcomes(raw_rule, on_pass, on_fail))
-        }
+    let rules = workflows_lut.get(name).ok_or_else(|| match path.last() {
+        Some(parent) => WorkflowError::Undefined {
+            workflow: name.to_string(),
+            referenced_by: parent.to_string(),
+        },
+        None => WorkflowError::MissingEntry(name.to_string()),
+    })?;
+
+    path.push(name);
+    // Every rule falls through to the ones after it, so build from the back.
+    let (fallback, conditional) = rules.split_last().unwrap();
+    let mut tree = compile_workflow(fallback, workflows_lut, path)?;
+    for rule in conditional.iter().rev() {
+        let (raw_rule, target) = rule.split_once(':').unwrap();
+        let on_pass = compile_workflow(target, workflows_lut, path)?;
+        tree = Box::new(Rule::new_with_outcomes(raw_rule, on_pass, tree));
     }
+    path.pop();
+
+    Ok(tree)
 }
 
-fn build_workflow_tree(workflows_lut: &HashMap<String, String>) -> Box<dyn Rated> {
-    add_rule("in0", workflows_lut)
+fn build_workflow_tree(raw_workflows: &str) -> Result<Box<dyn Rated>, WorkflowError> {
+    let workflows_lut = build_workflows_lookup(raw_workflows);
+    compile_workflow("in", &workflows_lut, &mut Vec::new())
 }
 
 // The order is x, m, a, s of course!
@@ -234,6 +384,25 @@ impl PartRange {
         let idx: usize = category.into();
         self.0[idx] = (low, high);
     }
+
+    fn is_empty(&self) -> bool {
+        self.0.iter().any(|rng| rng.0 > rng.1)
+    }
+
+    fn combinations(&self) -> usize {
+        self.0.iter().map(|rng| rng.1 - rng.0 + 1).product()
+    }
+}
+
+impl fmt::Display for PartRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ranges = "xmas"
+            .chars()
+            .zip(&self.0)
+            .map(|(category, rng)| format!("{category}={}..={}", rng.0, rng.1))
+            .collect::<Vec<String>>();
+        write!(f, "{}", ranges.join(" "))
+    }
 }
 
 fn main() {
@@ -241,8 +410,13 @@ fn main() {
         .split("\n\n")
         .collect::<Vec<&str>>();
 
-    let workflows_lut = build_workflows_lookup(workflows_parts[0]);
-    let workflows = build_workflow_tree(&workflows_lut);
+    let workflows = match build_workflow_tree(workflows_parts[0]) {
+        Ok(workflows) => workflows,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
 
     let part_1 = workflows_parts[1]
         .lines()
@@ -250,9 +424,73 @@ fn main() {
         .filter(|p| workflows.rate(p).is_accepted())
         .map(|p| p.sum_rating_numbers())
         .sum::<usize>();
-    println!("{part_1}");
+    println!("Part 1: {part_1}");
+
+    let mut accepted = Vec::new();
+    workflows.accepted_ranges(PartRange::new_with_range(1, 4000), &mut accepted);
+    let part_2 = accepted.iter().map(PartRange::combinations).sum::<usize>();
+    println!("Part 2: {part_2}");
+
+    if std::env::args().any(|arg| arg == "--tree") {
+        let pruned = workflows.prune(PartRange::new_with_range(1, 4000));
+        println!("\n{}", render_tree(pruned.as_ref()));
+        for part_range in accepted {
+            println!("{part_range}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = include_str!("../test-input");
+
+    fn accepted(tree: &dyn Rated) -> Vec<PartRange> {
+        let mut accepted = Vec::new();
+        tree.accepted_ranges(PartRange::new_with_range(1, 4000), &mut accepted);
+        accepted
+    }
 
-    let part_range = PartRange::new_with_range(1, 4000);
-    let part_2 = workflows.count_accepted(part_range);
-    println!("{part_2}");
+    #[test]
+    fn pruning_keeps_accepted_ranges() {
+        let workflows = EXAMPLE.split("\n\n").next().unwrap();
+        let tree = build_workflow_tree(workflows).unwrap();
+        let combinations =
+            |ranges: Vec<PartRange>| ranges.iter().map(PartRange::combinations).sum::<usize>();
+        assert_eq!(combinations(accepted(tree.as_ref())), 167409079868000);
+
+        let pruned = tree.prune(PartRange::new_with_range(1, 4000));
+        assert_eq!(accepted(pruned.as_ref()).len(), 7);
+        assert_eq!(combinations(accepted(pruned.as_ref())), 167409079868000);
+
+        // `lnx` accepts either way and `gd` rejects either way.
+        let rendered = render_tree(pruned.as_ref());
+        assert!(!rendered.contains("m>1548"));
+        assert!(!rendered.contains("a>3333"));
+    }
+
+    #[test]
+    fn less_than_zero_never_passes() {
+        let tree = build_workflow_tree("in{x<0:R,A}").unwrap();
+        assert_eq!(accepted(tree.as_ref()).len(), 1);
+        assert!(tree.prune(PartRange::new_with_range(1, 4000)).status() == Some(Status::Accepted));
+    }
+
+    #[test]
+    fn reports_broken_workflows() {
+        let error = |workflows| build_workflow_tree(workflows).err().unwrap().to_string();
+        assert_eq!(
+            error("in{x<5:A,ab}\nab{m>3:in,R}"),
+            "Workflows loop: in -> ab -> in"
+        );
+        assert_eq!(
+            error("in{x<5:A,ab}"),
+            "Workflow in sends parts to undefined workflow ab"
+        );
+        assert_eq!(
+            error("px{a<2006:qkq,m>2090:A,rfg}"),
+            "No workflow in for parts to start in"
+        );
+    }
 }
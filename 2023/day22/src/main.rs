@@ -1,14 +1,10 @@
-use std::collections::HashSet;
-
-type BrickCoords = HashSet<((usize, usize, usize), (usize, usize, usize))>;
+use std::collections::HashMap;
 
 #[derive(Debug, Clone)]
 struct Brick {
     c0: (usize, usize, usize),
     c1: (usize, usize, usize),
-    supported_by: BrickCoords,
 }
-
 impl From<&str> for Brick {
     fn from(value: &str) -> Self {
         let mut coords = value.split('~');
@@ -30,117 +26,214 @@ impl From<&str> for Brick {
         Self {
             c0: (c1[0], c1[1], c1[2]),
             c1: (c2[0], c2[1], c2[2]),
-            supported_by: HashSet::new(),
         }
     }
 }
 
 impl Brick {
-    fn collides_with(&self, other: &Brick) -> bool {
-        !(self.c0.0 > other.c1.0
-            || self.c1.0 < other.c0.0
-            || self.c0.1 > other.c1.1
-            || self.c1.1 < other.c0.1)
+    fn footprint(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (self.c0.0..=self.c1.0).flat_map(move |x| (self.c0.1..=self.c1.1).map(move |y| (x, y)))
     }
 
-    fn move_down(&mut self) {
-        self.c0.2 -= 1;
-        self.c1.2 -= 1;
+    fn height(&self) -> usize {
+        self.c1.2 - self.c0.2
     }
+}
+
+struct SupportGraph {
+    supports: Vec<Vec<usize>>,
+    supported_by: Vec<Vec<usize>>,
+    // Immediate dominators, `None` stands for the floor.
+    dominators: Vec<Option<usize>>,
+    dominated: Vec<Vec<usize>>,
+}
+
+/// Drops bricks (sorted by lowest z) onto a heightmap and records which bricks
+/// ended up resting on which. Brick IDs are indices into `bricks`.
+fn settle(bricks: &mut [Brick]) -> SupportGraph {
+    let mut heightmap: HashMap<(usize, usize), (usize, usize)> = HashMap::new();
+    let mut supports = vec![Vec::new(); bricks.len()];
+    let mut supported_by = vec![Vec::new(); bricks.len()];
+
+    for (id, brick) in bricks.iter_mut().enumerate() {
+        let top = brick
+            .footprint()
+            .filter_map(|cell| heightmap.get(&cell).map(|(z, _)| *z))
+            .max()
+            .unwrap_or(0);
+
+        let mut below = brick
+            .footprint()
+            .filter_map(|cell| heightmap.get(&cell))
+            .filter(|(z, _)| *z == top)
+            .map(|(_, other)| *other)
+            .collect::<Vec<usize>>();
+        below.sort_unstable();
+        below.dedup();
+
+        for other in &below {
+            supports[*other].push(id);
+        }
+        supported_by[id] = below;
 
-    fn on_the_floor(&self) -> bool {
-        self.c0.2 == 1
+        let height = brick.height();
+        brick.c0.2 = top + 1;
+        brick.c1.2 = top + 1 + height;
+        for cell in brick.footprint().collect::<Vec<(usize, usize)>>() {
+            heightmap.insert(cell, (brick.c1.2, id));
+        }
     }
 
-    fn register_support(&mut self, brick: &Brick) {
-        self.supported_by.insert((brick.c0, brick.c1));
+    let (dominators, dominated) = build_dominator_tree(&supported_by);
+    SupportGraph {
+        supports,
+        supported_by,
+        dominators,
+        dominated,
     }
+}
 
-    fn has_support(&self) -> bool {
-        !self.supported_by.is_empty()
+fn common_ancestor(
+    mut a: Option<usize>,
+    mut b: Option<usize>,
+    dominators: &[Option<usize>],
+    depth: &[usize],
+) -> Option<usize> {
+    while a != b {
+        if a.map_or(0, |n| depth[n]) >= b.map_or(0, |n| depth[n]) {
+            a = a.and_then(|n| dominators[n]);
+        } else {
+            b = b.and_then(|n| dominators[n]);
+        }
     }
+    a
 }
 
-fn settle(bricks: Vec<Brick>) -> Vec<Brick> {
-    let mut settled: Vec<Brick> = Vec::new();
-    for mut brick in bricks.into_iter() {
-        while !brick.has_support() {
-            if brick.on_the_floor() {
-                break;
-            }
+/// Settling order is already a topological order, so each brick's immediate
+/// dominator is the common ancestor of everything it rests on.
+fn build_dominator_tree(supported_by: &[Vec<usize>]) -> (Vec<Option<usize>>, Vec<Vec<usize>>) {
+    let mut dominators: Vec<Option<usize>> = vec![None; supported_by.len()];
+    let mut depth = vec![0; supported_by.len()];
+    let mut dominated = vec![Vec::new(); supported_by.len()];
+
+    for (id, below) in supported_by.iter().enumerate() {
+        let idom = match below.split_first() {
+            None => None,
+            Some((first, rest)) => rest.iter().try_fold(*first, |acc, other| {
+                common_ancestor(Some(acc), Some(*other), &dominators, &depth)
+            }),
+        };
+
+        dominators[id] = idom;
+        depth[id] = idom.map_or(1, |n| depth[n] + 1);
+        if let Some(parent) = idom {
+            dominated[parent].push(id);
+        }
+    }
 
-            let below = settled
-                .iter()
-                .filter(|b| b.c1.2 == brick.c0.2 - 1)
-                .collect::<Vec<&Brick>>();
+    (dominators, dominated)
+}
 
-            for brick_below in below {
-                if brick.collides_with(brick_below) {
-                    brick.register_support(brick_below);
-                }
-            }
+impl SupportGraph {
+    /// Every brick that falls if `brick` gets disintegrated.
+    fn falls_if_removed(&self, brick: usize) -> Vec<usize> {
+        let mut falling = Vec::new();
+        let mut stack = self.dominated[brick].clone();
+        while let Some(id) = stack.pop() {
+            falling.push(id);
+            stack.extend(self.dominated[id].iter().copied());
+        }
+        falling.sort_unstable();
+        falling
+    }
 
-            if !brick.has_support() {
-                brick.move_down();
+    /// Number of falling bricks for every possible removal, computed in one pass
+    /// over the dominator tree.
+    fn fall_counts(&self) -> Vec<usize> {
+        let mut subtree = vec![1; self.dominators.len()];
+        for id in (0..self.dominators.len()).rev() {
+            if let Some(parent) = self.dominators[id] {
+                subtree[parent] += subtree[id];
             }
         }
-        settled.push(brick.clone());
+        subtree.into_iter().map(|size| size - 1).collect()
     }
-    settled
-}
 
-fn get_single_supporting(bricks: &[Brick]) -> BrickCoords {
-    let mut required = HashSet::new();
-    for brick in bricks.iter() {
-        if brick.supported_by.len() == 1 {
-            required.extend(&brick.supported_by);
+    fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph bricks {\n    floor [shape=box];\n");
+        for (id, below) in self.supported_by.iter().enumerate() {
+            if below.is_empty() {
+                dot.push_str(&format!("    floor -> {id};\n"));
+            }
         }
+        for (id, above) in self.supports.iter().enumerate() {
+            for other in above {
+                dot.push_str(&format!("    {id} -> {other};\n"));
+            }
+        }
+        dot.push_str("}\n");
+        dot
     }
-    required
 }
 
-fn count_removable(bricks: &[Brick]) -> usize {
-    bricks.len() - get_single_supporting(bricks).len()
+fn parse_bricks(raw_bricks: &str) -> Vec<Brick> {
+    let mut bricks = raw_bricks.lines().map(Brick::from).collect::<Vec<Brick>>();
+    bricks.sort_by_key(|b| b.c0.2);
+    bricks
 }
 
-fn count_falling(bricks: &[Brick]) -> usize {
-    let mut all_falling = 0;
-    for support in get_single_supporting(bricks) {
-        let mut falling = HashSet::new();
-        falling.insert(support);
-
-        loop {
-            let mut still_falling = false;
-            for brick in bricks {
-                if falling.contains(&(brick.c0, brick.c1)) {
-                    continue;
-                }
-
-                if !brick.supported_by.is_empty() && brick.supported_by.is_subset(&falling) {
-                    falling.insert((brick.c0, brick.c1));
-                    still_falling = true;
-                }
-            }
-
-            if !still_falling {
-                break;
+fn main() {
+    let mut bricks = parse_bricks(include_str!("../input"));
+    let graph = settle(&mut bricks);
+    let fall_counts = graph.fall_counts();
+
+    let part_1 = fall_counts.iter().filter(|count| **count == 0).count();
+    println!("Part 1: {part_1}");
+
+    let part_2 = fall_counts.iter().sum::<usize>();
+    println!("Part 2: {part_2}");
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--falls" => {
+                let brick = args
+                    .next()
+                    .and_then(|id| id.parse().ok())
+                    .filter(|id| *id < bricks.len())
+                    .expect("Expected a brick id");
+                println!(
+                    "\nRemoving brick {brick} drops {:?}",
+                    graph.falls_if_removed(brick)
+                );
             }
+            "--dot" => print!("{}", graph.to_dot()),
+            _ => {}
         }
-
-        all_falling += falling.len() - 1;
     }
-    all_falling
 }
 
-fn main() {
-    let mut bricks = include_str!("../input")
-        .lines()
-        .map(Brick::from)
-        .collect::<Vec<Brick>>();
-
-    bricks.sort_by_key(|b| b.c0.2);
-    let settled = settle(bricks);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn example_falls() {
+        let mut bricks = parse_bricks(include_str!("../test-input"));
+        let graph = settle(&mut bricks);
+        assert_eq!(graph.fall_counts(), vec![6, 0, 0, 0, 0, 1, 0]);
+        assert_eq!(graph.falls_if_removed(0), vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(graph.falls_if_removed(5), vec![6]);
+        assert!(graph.falls_if_removed(1).is_empty());
+    }
 
-    println!("Part 1: {}", count_removable(&settled));
-    println!("Part 2: {}", count_falling(&settled));
+    #[test]
+    fn example_dot() {
+        let mut bricks = parse_bricks(include_str!("../test-input"));
+        let dot = settle(&mut bricks).to_dot();
+        assert!(dot.starts_with("digraph bricks {\n    floor [shape=box];\n    floor -> 0;\n"));
+        for edge in ["0 -> 1", "0 -> 2", "1 -> 3", "2 -> 4", "4 -> 5", "5 -> 6"] {
+            assert!(dot.contains(edge), "{edge} missing from {dot}");
+        }
+    }
 }
@@ -2,7 +2,7 @@ use std::cmp::max;
 use std::collections::{HashMap, HashSet, VecDeque};
 
 type Map = HashMap<(i32, i32), char>;
-type CompressedMap = HashMap<(i32, i32), Vec<((i32, i32), i32)>>;
+type Tiles = Vec<(i32, i32)>;
 
 const DIRECTIONS: &[(i32, i32)] = &[(1, 0), (0, 1), (-1, 0), (0, -1)];
 
@@ -39,9 +39,9 @@ fn walk(
         };
 
         if &forced_new != previous {
-            return walk(map, current, &forced_new, finish, steps + 1);
+            walk(map, current, &forced_new, finish, steps + 1)
         } else {
-            return 0;
+            0
         }
     } else {
         for dir in DIRECTIONS {
@@ -84,62 +84,94 @@ fn find_junctions(map: &Map) -> Vec<(i32, i32)> {
     junctions
 }
 
+struct Edge {
+    to: usize,
+    steps: usize,
+    tiles: Tiles,
+}
+
+struct CompressedMap {
+    junctions: Vec<(i32, i32)>,
+    edges: Vec<Vec<Edge>>,
+    start: usize,
+    finish: usize,
+}
+
+#[derive(Clone, Default)]
+struct Route {
+    steps: usize,
+    junctions: Vec<usize>,
+}
+
 fn compress_map(
     map: &Map,
     junctions: &[(i32, i32)],
     start: &(i32, i32),
     finish: &(i32, i32),
 ) -> CompressedMap {
-    let mut compressed_map = HashMap::new();
-    compressed_map.insert(*start, distance_to_next_junctions(map, start));
-    for j in junctions.iter().copied() {
-        compressed_map.insert(j, distance_to_next_junctions(map, &j));
-    }
+    // Junctions are re-indexed to dense IDs so that the visited set fits in a single u64.
+    let mut nodes = vec![*start];
+    nodes.extend(junctions.iter().copied());
+    nodes.push(*finish);
+    assert!(nodes.len() <= 64, "too many junctions for a u64 bitmask");
+
+    let ids = nodes
+        .iter()
+        .enumerate()
+        .map(|(id, pos)| (*pos, id))
+        .collect::<HashMap<(i32, i32), usize>>();
+
+    let mut edges = nodes
+        .iter()
+        .map(|pos| {
+            distance_to_next_junctions(map, pos)
+                .into_iter()
+                .map(|(to, tiles)| Edge {
+                    to: ids[&to],
+                    steps: tiles.len(),
+                    tiles,
+                })
+                .collect::<Vec<Edge>>()
+        })
+        .collect::<Vec<Vec<Edge>>>();
 
     // DFS optimization - last junction has to lead to the finish, otherwise we'd violate
     // "never step onto the same tile twice" rule.
-    let mut last_junction = None;
-    for (j, neighbors) in &compressed_map {
-        if neighbors
-            .iter()
-            .map(|n| n.0)
-            .collect::<Vec<(i32, i32)>>()
-            .contains(finish)
-        {
-            last_junction = Some(j);
-        }
-    }
-
-    compressed_map
-        .entry(*last_junction.unwrap())
-        .and_modify(|e| {
-            *e = e
-                .iter()
-                .cloned()
-                .filter(|n| &n.0 == finish)
-                .collect::<Vec<((i32, i32), i32)>>();
-        });
+    let finish_id = nodes.len() - 1;
+    let last_junction = edges
+        .iter()
+        .position(|neighbors| neighbors.iter().any(|e| e.to == finish_id))
+        .unwrap();
+    edges[last_junction].retain(|e| e.to == finish_id);
 
-    compressed_map
+    CompressedMap {
+        junctions: nodes,
+        edges,
+        start: 0,
+        finish: finish_id,
+    }
 }
 
-fn distance_to_next_junctions(map: &Map, start: &(i32, i32)) -> Vec<((i32, i32), i32)> {
+fn distance_to_next_junctions(map: &Map, start: &(i32, i32)) -> Vec<((i32, i32), Tiles)> {
     let mut queue = VecDeque::new();
+    let mut parents = HashMap::new();
+    let mut seen = HashSet::new();
+    seen.insert(*start);
+
     for offset in DIRECTIONS {
         let neighbor = (start.0 + offset.0, start.1 + offset.1);
         if let Some(n) = map.get(&neighbor) {
             if ['>', '<', '^', 'v', '.'].contains(n) {
-                queue.push_back((neighbor, 1));
+                seen.insert(neighbor);
+                parents.insert(neighbor, *start);
+                queue.push_back(neighbor);
             }
         }
     }
 
-    let mut seen = HashSet::new();
-    seen.insert(*start);
-
     let mut found = Vec::new();
 
-    while let Some((pos, dist)) = queue.pop_front() {
+    while let Some(pos) = queue.pop_front() {
         for offset in DIRECTIONS {
             let neighbor = (pos.0 + offset.0, pos.1 + offset.1);
             if seen.contains(&neighbor) {
@@ -149,16 +181,15 @@ fn distance_to_next_junctions(map: &Map, start: &(i32, i32)) -> Vec<((i32, i32),
             if let Some(n) = map.get(&neighbor) {
                 seen.insert(neighbor);
                 if ['>', '<', '^', 'v', '.'].contains(n) {
+                    parents.insert(neighbor, pos);
                     if is_junction(map, &neighbor) {
-                        found.push((neighbor, dist + 1));
+                        found.push((neighbor, trace_tiles(&parents, start, &neighbor)));
                     } else {
-                        queue.push_back((neighbor, dist + 1));
+                        queue.push_back(neighbor);
                     }
                 }
-            } else {
-                if neighbor.0 > 0 {
-                    found.push(((neighbor.0 - 1, neighbor.1), dist));
-                }
+            } else if neighbor.0 > 0 {
+                found.push((pos, trace_tiles(&parents, start, &pos)));
             }
         }
     }
@@ -166,6 +197,24 @@ fn distance_to_next_junctions(map: &Map, start: &(i32, i32)) -> Vec<((i32, i32),
     found
 }
 
+fn trace_tiles(
+    parents: &HashMap<(i32, i32), (i32, i32)>,
+    start: &(i32, i32),
+    end: &(i32, i32),
+) -> Tiles {
+    let mut tiles = vec![*end];
+    let mut current = *end;
+    while let Some(parent) = parents.get(&current) {
+        if parent == start {
+            break;
+        }
+        tiles.push(*parent);
+        current = *parent;
+    }
+    tiles.reverse();
+    tiles
+}
+
 fn is_junction(map: &Map, pos: &(i32, i32)) -> bool {
     for offset in DIRECTIONS {
         let neighbor = (pos.0 + offset.0, pos.1 + offset.1);
@@ -180,39 +229,138 @@ fn is_junction(map: &Map, pos: &(i32, i32)) -> bool {
 
 fn walk_compressed_map(
     map: &CompressedMap,
-    current: &(i32, i32),
-    previous: Vec<(i32, i32)>,
-    finish: &(i32, i32),
+    current: usize,
+    visited: u64,
     steps: usize,
-) -> usize {
-    if current == finish {
-        return steps;
+    route: &mut Vec<usize>,
+    best: &mut Route,
+) {
+    if current == map.finish {
+        if steps > best.steps {
+            best.steps = steps;
+            best.junctions.clone_from(route);
+        }
+        return;
     }
 
-    let mut longest = 0;
-    for neighbor in map.get(current).unwrap() {
-        if previous.contains(&neighbor.0) {
+    for edge in &map.edges[current] {
+        if visited & (1 << edge.to) != 0 {
             continue;
         }
 
-        let mut new_previous = previous.clone();
-        new_previous.push(neighbor.0);
-        let dst = walk_compressed_map(
+        route.push(edge.to);
+        walk_compressed_map(
             map,
-            &neighbor.0,
-            new_previous,
-            finish,
-            steps + neighbor.1 as usize,
+            edge.to,
+            visited | (1 << edge.to),
+            steps + edge.steps,
+            route,
+            best,
         );
-        longest = max(longest, dst);
+        route.pop();
     }
-    longest
 }
 
-fn main() {
-    let raw_map = include_str!("../input");
+fn find_longest_route(map: &CompressedMap, parallel: bool) -> Route {
+    let mut best = Route::default();
+    if !parallel {
+        let mut route = vec![map.start];
+        walk_compressed_map(map, map.start, 1 << map.start, 0, &mut route, &mut best);
+        return best;
+    }
 
-    let map = build_map(raw_map);
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+
+    // Unroll the first few levels of the DFS breadth-first, so that each thread
+    // gets a fair share of partial routes to finish.
+    let mut prefixes = vec![(map.start, 1u64 << map.start, 0, vec![map.start])];
+    while prefixes.len() < threads * 8 {
+        let mut expanded = Vec::new();
+        for (current, visited, steps, route) in &prefixes {
+            if *current == map.finish {
+                expanded.push((*current, *visited, *steps, route.clone()));
+                continue;
+            }
+            for edge in &map.edges[*current] {
+                if visited & (1 << edge.to) == 0 {
+                    let mut new_route = route.clone();
+                    new_route.push(edge.to);
+                    expanded.push((
+                        edge.to,
+                        visited | (1 << edge.to),
+                        steps + edge.steps,
+                        new_route,
+                    ));
+                }
+            }
+        }
+
+        if expanded.len() <= prefixes.len() {
+            prefixes = expanded;
+            break;
+        }
+        prefixes = expanded;
+    }
+
+    std::thread::scope(|scope| {
+        let handles = (0..threads)
+            .map(|thread| {
+                let prefixes = &prefixes;
+                scope.spawn(move || {
+                    let mut best = Route::default();
+                    for (current, visited, steps, route) in
+                        prefixes.iter().skip(thread).step_by(threads)
+                    {
+                        let mut route = route.clone();
+                        walk_compressed_map(map, *current, *visited, *steps, &mut route, &mut best);
+                    }
+                    best
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for handle in handles {
+            let candidate = handle.join().unwrap();
+            if candidate.steps > best.steps {
+                best = candidate;
+            }
+        }
+    });
+
+    best
+}
+
+fn render_route(raw_map: &str, map: &CompressedMap, route: &Route) -> String {
+    let mut tiles = HashSet::new();
+    for pair in route.junctions.windows(2) {
+        let edge = map.edges[pair[0]]
+            .iter()
+            .filter(|e| e.to == pair[1])
+            .max_by_key(|e| e.steps)
+            .unwrap();
+        tiles.extend(edge.tiles.iter().copied());
+    }
+
+    let start = map.junctions[map.start];
+    let mut rendered = String::new();
+    for (row, line) in raw_map.lines().enumerate() {
+        for (col, char) in line.chars().enumerate() {
+            let pos = (row as i32, col as i32);
+            if pos == start {
+                rendered.push('S');
+            } else if tiles.contains(&pos) {
+                rendered.push('O');
+            } else {
+                rendered.push(char);
+            }
+        }
+        rendered.push('\n');
+    }
+    rendered
+}
+
+/// The only open tiles on the top and bottom rows.
+fn find_entry_and_exit(map: &Map) -> ((i32, i32), (i32, i32)) {
     let entry_vec = map
         .iter()
         .filter(|p| p.0 .0 == 0)
@@ -235,11 +383,48 @@ fn main() {
         .collect::<Vec<&(i32, i32)>>();
     let exit = exit_vec[0];
 
-    let part_1 = walk(&map, entry, entry, exit, 0);
+    (*entry, *exit)
+}
+
+fn main() {
+    let raw_map = include_str!("../input");
+
+    let map = build_map(raw_map);
+    let (entry, exit) = find_entry_and_exit(&map);
+
+    let part_1 = walk(&map, &entry, &entry, &exit, 0);
     println!("Part 1: {part_1}");
 
     let junctions = find_junctions(&map);
-    let compressed_map = compress_map(&map, &junctions, entry, exit);
-    let part_2 = walk_compressed_map(&compressed_map, entry, Vec::new(), exit, 0);
-    println!("Part 2: {part_2}");
+    let compressed_map = compress_map(&map, &junctions, &entry, &exit);
+    let route = find_longest_route(&compressed_map, true);
+    println!("Part 2: {}", route.steps);
+    if std::env::args().any(|arg| arg == "--render") {
+        print!("{}", render_route(raw_map, &compressed_map, &route));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = include_str!("../test-input");
+
+    #[test]
+    fn example() {
+        let map = build_map(EXAMPLE);
+        let (entry, exit) = find_entry_and_exit(&map);
+        assert_eq!((entry, exit), ((0, 1), (22, 21)));
+        assert_eq!(walk(&map, &entry, &entry, &exit, 0), 94);
+
+        let compressed_map = compress_map(&map, &find_junctions(&map), &entry, &exit);
+        for parallel in [false, true] {
+            let route = find_longest_route(&compressed_map, parallel);
+            assert_eq!(route.steps, 154);
+
+            let rendered = render_route(EXAMPLE, &compressed_map, &route);
+            assert_eq!(rendered.matches('O').count(), 154);
+            assert!(rendered.starts_with("#S#"));
+        }
+    }
 }
@@ -1,7 +1,7 @@
-use std::cmp::{max, min, Ordering};
+use std::cmp::Ordering;
 use std::collections::HashMap;
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 enum HandType {
     HighCard,
     OnePair,
@@ -12,78 +12,127 @@ enum HandType {
     FiveKind,
 }
 
-type Mapper = dyn Fn(usize, usize, usize) -> HandType;
-
 impl HandType {
-    fn from_mapped_cards(value: &str, mapper: &Mapper) -> Self {
+    /// Classifies a hand from how often each card shows up, most common first.
+    fn from_counts(counts: &[usize]) -> Self {
+        match (counts[0], counts.get(1).unwrap_or(&0)) {
+            (5, _) => HandType::FiveKind,
+            (4, _) => HandType::FourKind,
+            (3, 2) => HandType::FullHouse,
+            (3, _) => HandType::ThreeKind,
+            (2, 2) => HandType::TwoPair,
+            (2, _) => HandType::OnePair,
+            _ => HandType::HighCard,
+        }
+    }
+}
+
+/// Everything that differs between variants of Camel Cards.
+trait Ruleset {
+    /// All cards, from weakest to strongest.
+    fn card_order(&self) -> &str;
+
+    /// Cards that pretend to be whatever makes the hand strongest.
+    fn wildcards(&self) -> &str {
+        ""
+    }
+
+    fn strength(&self, card: char) -> usize {
+        self.card_order()
+            .find(card)
+            .unwrap_or_else(|| panic!("Unknown card {card}"))
+    }
+
+    fn classify(&self, cards: &str) -> HandType {
         let mut card_counts = HashMap::new();
-        value.chars().for_each(|char| {
-            card_counts
-                .entry(char)
-                .and_modify(|count| *count += 1)
-                .or_insert(1);
-        });
-
-        let n_jokers = card_counts.remove(&'J').unwrap_or(0);
-        let highest = card_counts.values().max().unwrap_or(&0).to_owned();
-        let n_cards = card_counts.len();
-        mapper(highest, n_cards, n_jokers)
+        let mut n_wild = 0;
+        for card in cards.chars() {
+            if self.wildcards().contains(card) {
+                n_wild += 1;
+            } else {
+                *card_counts.entry(card).or_insert(0) += 1;
+            }
+        }
+
+        // Wildcards are always best spent on the most common card.
+        let mut counts = card_counts.into_values().collect::<Vec<usize>>();
+        counts.sort_unstable_by(|a, b| b.cmp(a));
+        match counts.first_mut() {
+            Some(highest) => *highest += n_wild,
+            None => counts.push(n_wild),
+        }
+        HandType::from_counts(&counts)
     }
 }
 
-fn regular_rules(highest: usize, n_cards: usize, n_jokers: usize) -> HandType {
-    match (max(highest, n_jokers), n_cards + min(1, n_jokers)) {
-        (5, _) => HandType::FiveKind,
-        (4, _) => HandType::FourKind,
-        (3, 2) => HandType::FullHouse,
-        (3, 3) => HandType::ThreeKind,
-        (2, 3) => HandType::TwoPair,
-        (2, 4) => HandType::OnePair,
-        _ => HandType::HighCard,
+struct Regular;
+
+impl Ruleset for Regular {
+    fn card_order(&self) -> &str {
+        "23456789TJQKA"
     }
 }
 
-fn joker_rules(highest: usize, n_cards: usize, n_jokers: usize) -> HandType {
-    match (highest + n_jokers, n_cards, n_jokers) {
-        (5, _, _) => HandType::FiveKind,
-        (4, _, _) => HandType::FourKind,
-        (3, 2, _) => HandType::FullHouse,
-        (3, 3, _) => HandType::ThreeKind,
-        (2, 3, 0) => HandType::TwoPair,
-        (2, 4, 0) => HandType::OnePair,
-        (_, _, 1..=5) => HandType::OnePair,
-        (_, _, 0) => HandType::HighCard,
-        _ => unreachable!(),
+struct Jokers;
+
+impl Ruleset for Jokers {
+    fn card_order(&self) -> &str {
+        "J23456789TQKA"
+    }
+
+    fn wildcards(&self) -> &str {
+        "J"
     }
 }
 
 #[derive(Debug, PartialEq, Eq)]
 struct Hand {
-    // T: 10, J: 11 | 1, Q: 12, K: 13, A: 14, 9: 9, ...
+    label: String,
+    // Strength of each card under the ruleset the hand was dealt with.
     cards: Vec<usize>,
     bid: usize,
     type_: HandType,
 }
 
 impl Hand {
-    fn from_mapped_cards(value: &str, mapper: &Mapper) -> Self {
+    fn new(value: &str, rules: &dyn Ruleset) -> Self {
         let values = value.split(' ').collect::<Vec<&str>>();
         let bid = values.last().unwrap().parse::<usize>().unwrap();
-
         let cards = values.first().unwrap().to_owned();
-        let hand_type = HandType::from_mapped_cards(cards, mapper);
-
-        // Query mapper to see what value needs to represent a Joker. Kinda hacky.
-        let parsed_cards = match mapper(0, 0, 1) {
-            HandType::HighCard => parse_cards(cards, 11),
-            HandType::OnePair => parse_cards(cards, 1),
-            _ => unreachable!(),
-        };
 
         Hand {
-            cards: parsed_cards,
+            label: cards.to_string(),
+            cards: cards.chars().map(|card| rules.strength(card)).collect(),
             bid,
-            type_: hand_type,
+            type_: rules.classify(cards),
+        }
+    }
+
+    /// Explains why this hand beats a weaker one.
+    fn explain(&self, weaker: &Hand) -> String {
+        if self.type_ != weaker.type_ {
+            return format!(
+                "{} beats {}: {:?} beats {:?}",
+                self.label, weaker.label, self.type_, weaker.type_
+            );
+        }
+
+        match self
+            .cards
+            .iter()
+            .zip(&weaker.cards)
+            .position(|(a, b)| a != b)
+        {
+            Some(idx) => format!(
+                "{} beats {}: both {:?}, card {} {} beats {}",
+                self.label,
+                weaker.label,
+                self.type_,
+                idx + 1,
+                self.label.chars().nth(idx).unwrap(),
+                weaker.label.chars().nth(idx).unwrap()
+            ),
+            None => format!("{} ties with {}", self.label, weaker.label),
         }
     }
 }
@@ -104,32 +153,17 @@ impl PartialOrd for Hand {
     }
 }
 
-fn parse_cards(cards: &str, joker_value: usize) -> Vec<usize> {
-    let mut parsed = Vec::new();
-    for card in cards.chars() {
-        match card {
-            'T' => parsed.push(10),
-            'J' => parsed.push(joker_value),
-            'Q' => parsed.push(12),
-            'K' => parsed.push(13),
-            'A' => parsed.push(14),
-            '2'..='9' => parsed.push(card.to_digit(10).unwrap() as usize),
-            _ => unreachable!(),
-        }
-    }
-
-    parsed
-}
-
-fn total_winnings(lines: &[&str], mapper: &Mapper) -> usize {
+fn rank_hands(lines: &[&str], rules: &dyn Ruleset) -> Vec<Hand> {
     let mut hands = lines
         .iter()
-        .map(|cards| Hand::from_mapped_cards(cards, mapper))
+        .map(|cards| Hand::new(cards, rules))
         .collect::<Vec<Hand>>();
-
     hands.sort();
-
     hands
+}
+
+fn total_winnings(lines: &[&str], rules: &dyn Ruleset) -> usize {
+    rank_hands(lines, rules)
         .iter()
         .enumerate()
         .map(|(rank, hand)| hand.bid * (rank + 1))
@@ -138,6 +172,66 @@ fn total_winnings(lines: &[&str], mapper: &Mapper) -> usize {
 
 fn main() {
     let lines = include_str!("../input").lines().collect::<Vec<&str>>();
-    println!("Part 1: {}", total_winnings(&lines, &regular_rules));
-    println!("Part 2: {}", total_winnings(&lines, &joker_rules));
+    println!("Part 1: {}", total_winnings(&lines, &Regular));
+    println!("Part 2: {}", total_winnings(&lines, &Jokers));
+
+    // `--explain` walks down the Part 2 ranking, one pair of neighbours at a time.
+    if std::env::args().any(|arg| arg == "--explain") {
+        let hands = rank_hands(&lines, &Jokers);
+        println!();
+        for pair in hands.windows(2).rev() {
+            println!("{}", pair[1].explain(&pair[0]));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = include_str!("../test-input");
+
+    /// Jokers and deuces are both wild, and aces are low.
+    struct WildDeuces;
+
+    impl Ruleset for WildDeuces {
+        fn card_order(&self) -> &str {
+            "2JA3456789TQK"
+        }
+
+        fn wildcards(&self) -> &str {
+            "2J"
+        }
+    }
+
+    #[test]
+    fn example_winnings() {
+        let lines = EXAMPLE.lines().collect::<Vec<&str>>();
+        assert_eq!(total_winnings(&lines, &Regular), 6440);
+        assert_eq!(total_winnings(&lines, &Jokers), 5905);
+        assert_eq!(total_winnings(&lines, &WildDeuces), 6642);
+    }
+
+    #[test]
+    fn explains_rankings() {
+        let lines = EXAMPLE.lines().collect::<Vec<&str>>();
+        let hands = rank_hands(&lines, &WildDeuces);
+        let explanations = hands
+            .windows(2)
+            .rev()
+            .map(|pair| pair[1].explain(&pair[0]))
+            .collect::<Vec<String>>();
+        assert_eq!(
+            explanations,
+            vec![
+                "KTJJT beats QQQJA: both FourKind, card 1 K beats Q",
+                "QQQJA beats T55J5: both FourKind, card 1 Q beats T",
+                "T55J5 beats 32T3K: FourKind beats ThreeKind",
+                "32T3K beats KK677: ThreeKind beats TwoPair",
+            ]
+        );
+
+        let twin = Hand::new("KTJJT 1", &WildDeuces);
+        assert_eq!(twin.explain(&hands[4]), "KTJJT ties with KTJJT");
+    }
 }
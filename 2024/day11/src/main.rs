@@ -1,69 +1,141 @@
+use num::BigUint;
 use std::collections::HashMap;
 
-fn populate_stones(data: &str) -> HashMap<String, usize> {
+/// Number of stones with each engraving. Counts grow exponentially with every
+/// blink, hence the big integers.
+type Distribution = HashMap<u64, BigUint>;
+
+fn digits(value: u64) -> u32 {
+    value.checked_ilog10().map_or(1, |log| log + 1)
+}
+
+fn populate_stones(data: &str) -> Distribution {
     let mut stones = HashMap::new();
     for num in data.split_whitespace() {
-        stones
-            .entry(num.to_string())
-            .and_modify(|v| *v += 1)
-            .or_insert(1);
+        *stones
+            .entry(num.parse::<u64>().unwrap())
+            .or_insert(BigUint::ZERO) += 1u32;
     }
     stones
 }
 
-fn blink(init_stones: &HashMap<String, usize>, times: usize) -> usize {
-    let mut stones = init_stones.clone();
-
-    for _ in 0..times {
-        let mut new_stones: HashMap<String, usize> = HashMap::new();
-        for (stone, cnt) in stones.iter() {
-            if stone == "0" {
-                new_stones
-                    .entry("1".to_string())
-                    .and_modify(|v| *v += cnt)
-                    .or_insert(*cnt);
-            } else if stone.len() % 2 == 0 {
-                let (left, right) = stone.split_at(stone.len() / 2);
-                new_stones
-                    .entry(left.to_string())
-                    .and_modify(|v| *v += cnt)
-                    .or_insert(*cnt);
-
-                let right_trimmed = right.trim_start_matches('0').to_string();
-                if right_trimmed.is_empty() {
-                    new_stones
-                        .entry("0".to_string())
-                        .and_modify(|v| *v += cnt)
-                        .or_insert(*cnt);
-                } else {
-                    new_stones
-                        .entry(right_trimmed)
-                        .and_modify(|v| *v += cnt)
-                        .or_insert(*cnt);
-                }
+#[derive(Default)]
+struct Blinker {
+    // What a single stone turns into after one blink.
+    transitions: HashMap<u64, (u64, Option<u64>)>,
+    // How many stones a single stone turns into after a number of blinks.
+    counts: HashMap<(u64, usize), BigUint>,
+}
+
+impl Blinker {
+    fn transition(&mut self, stone: u64) -> (u64, Option<u64>) {
+        *self.transitions.entry(stone).or_insert_with(|| {
+            let len = digits(stone);
+            if stone == 0 {
+                (1, None)
+            } else if len.is_multiple_of(2) {
+                let half = 10u64.pow(len / 2);
+                (stone / half, Some(stone % half))
             } else {
-                let n = stone.parse::<isize>().unwrap() * 2024;
-                new_stones
-                    .entry(n.to_string())
-                    .and_modify(|v| *v += cnt)
-                    .or_insert(*cnt);
+                (stone.checked_mul(2024).expect("stone value overflow"), None)
             }
+        })
+    }
+
+    fn count(&mut self, stone: u64, blinks: usize) -> BigUint {
+        if blinks == 0 {
+            return BigUint::from(1u32);
+        }
+        if let Some(count) = self.counts.get(&(stone, blinks)) {
+            return count.clone();
         }
 
-        stones = new_stones;
+        let (left, right) = self.transition(stone);
+        let mut count = self.count(left, blinks - 1);
+        if let Some(right) = right {
+            count += self.count(right, blinks - 1);
+        }
+        self.counts.insert((stone, blinks), count.clone());
+        count
+    }
+
+    fn total(&mut self, stones: &Distribution, blinks: usize) -> BigUint {
+        stones
+            .iter()
+            .map(|(stone, cnt)| self.count(*stone, blinks) * cnt)
+            .sum()
     }
 
-    stones.values().sum()
+    /// Blinks the whole distribution forward. Unlike `count` this only keeps a
+    /// single generation around, so it scales to thousands of blinks.
+    fn distribution(&mut self, stones: &Distribution, blinks: usize) -> Distribution {
+        let mut stones = stones.clone();
+        for _ in 0..blinks {
+            let mut new_stones: Distribution = HashMap::new();
+            for (stone, cnt) in stones {
+                let (left, right) = self.transition(stone);
+                if let Some(right) = right {
+                    *new_stones.entry(right).or_insert(BigUint::ZERO) += &cnt;
+                }
+                *new_stones.entry(left).or_insert(BigUint::ZERO) += cnt;
+            }
+            stones = new_stones;
+        }
+        stones
+    }
 }
 
 fn main() {
     let data = include_str!("../input");
 
     let stones = populate_stones(data);
+    let mut blinker = Blinker::default();
 
-    let part_1 = blink(&stones, 25);
+    let part_1 = blinker.total(&stones, 25);
     println!("Part 1: {part_1}");
 
-    let part_2 = blink(&stones, 75);
+    let part_2 = blinker.total(&stones, 75);
     println!("Part 2: {part_2}");
+
+    // `--blinks 2000` summarises the stones after many more blinks.
+    let mut args = std::env::args().skip_while(|arg| arg != "--blinks").skip(1);
+    if let Some(blinks) = args.next() {
+        let blinks = blinks.parse().expect("Expected a number of blinks");
+        let distribution = blinker.distribution(&stones, blinks);
+        let total = distribution.values().sum::<BigUint>();
+        let most_common = distribution.iter().max_by_key(|(_, cnt)| *cnt).unwrap().0;
+        println!(
+            "\nAfter {blinks} blinks: {} digits worth of stones, {} distinct, most common {most_common}",
+            total.to_string().len(),
+            distribution.len(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_digits() {
+        assert_eq!(digits(0), 1);
+        assert_eq!(digits(9), 1);
+        assert_eq!(digits(10), 2);
+        assert_eq!(digits(2024), 4);
+    }
+
+    #[test]
+    fn example_blinks() {
+        let stones = populate_stones("125 17");
+        let mut blinker = Blinker::default();
+        assert_eq!(blinker.total(&stones, 6), BigUint::from(22u32));
+        assert_eq!(blinker.total(&stones, 25), BigUint::from(55312u32));
+
+        let distribution = blinker.distribution(&stones, 6);
+        let expected =
+            populate_stones("2097446912 14168 4048 2 0 2 4 40 48 2024 40 48 80 96 2 8 6 7 6 0 3 2");
+        assert_eq!(distribution, expected);
+        let total = blinker.distribution(&stones, 25).values().sum::<BigUint>();
+        assert_eq!(total, BigUint::from(55312u32));
+    }
 }
@@ -1,191 +1,281 @@
-use std::collections::{HashMap, HashSet};
-use uuid::Uuid;
+use std::collections::VecDeque;
 
 const DIRECTIONS: [(i32, i32); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+const DIAGONALS: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
 
 #[derive(Debug)]
-struct Plant {
+struct Region {
+    id: usize,
     plant_type: char,
-    region_id: Option<Uuid>,
+    area: usize,
     perimeter: usize,
+    sides: usize,
+    // Top left and bottom right corners, inclusive.
+    bounding_box: ((i32, i32), (i32, i32)),
+    holes: usize,
 }
 
-impl Plant {
-    fn from_type(plant_type: char) -> Self {
-        Self {
-            plant_type,
-            region_id: None,
-            perimeter: 0,
-        }
-    }
-}
-
-fn build_garden(data: &str) -> HashMap<(i32, i32), Plant> {
-    let mut garden = HashMap::new();
-    for (row, line) in data.lines().enumerate() {
-        for (col, plant) in line.chars().enumerate() {
-            garden.insert((row as i32, col as i32), Plant::from_type(plant));
-        }
-    }
-
-    garden
+struct Garden {
+    plants: Vec<Vec<char>>,
+    labels: Vec<Vec<usize>>,
+    regions: Vec<Region>,
 }
 
-fn get_garden_bounds(garden: &HashMap<(i32, i32), Plant>) -> (i32, i32) {
-    let maxrow = garden.keys().map(|k| k.0).max().unwrap();
-    let maxcol = garden.keys().map(|k| k.1).max().unwrap();
-    (maxrow, maxcol)
-}
+impl Garden {
+    fn build(data: &str) -> Self {
+        let plants = data
+            .lines()
+            .map(|line| line.chars().collect())
+            .collect::<Vec<Vec<char>>>();
 
-fn find_perimeters(garden: &mut HashMap<(i32, i32), Plant>) {
-    let (maxrow, maxcol) = get_garden_bounds(garden);
+        let mut garden = Self {
+            labels: vec![vec![usize::MAX; plants[0].len()]; plants.len()],
+            plants,
+            regions: Vec::new(),
+        };
+        garden.label_regions();
+        garden
+    }
 
-    for row in 0..=maxrow {
-        for col in 0..=maxcol {
-            let plant = garden.get(&(row, col)).unwrap();
-            let p = find_perimeter(plant, (row, col), garden);
-            garden.entry((row, col)).and_modify(|e| e.perimeter = p);
+    fn plant(&self, pos: (i32, i32)) -> Option<char> {
+        if pos.0 < 0 || pos.1 < 0 {
+            return None;
         }
+        self.plants
+            .get(pos.0 as usize)
+            .and_then(|row| row.get(pos.1 as usize))
+            .copied()
     }
-}
 
-fn find_perimeter(plant: &Plant, coords: (i32, i32), garden: &HashMap<(i32, i32), Plant>) -> usize {
-    let mut total = 0;
-    for direction in DIRECTIONS {
-        if let Some(neighbnor) = garden.get(&(coords.0 + direction.0, coords.1 + direction.1)) {
-            if neighbnor.plant_type == plant.plant_type {
-                continue;
-            }
-        }
-        total += 1;
+    fn in_region(&self, pos: (i32, i32), id: usize) -> bool {
+        self.plant(pos).is_some() && self.labels[pos.0 as usize][pos.1 as usize] == id
     }
-    total
-}
 
-fn group_regions(garden: &mut HashMap<(i32, i32), Plant>) {
-    let (maxrow, maxcol) = get_garden_bounds(garden);
-
-    for row in 0..=maxrow {
-        for col in 0..=maxcol {
-            let plant = garden.get(&(row, col)).unwrap();
-            let up = garden.get(&(row - 1, col));
-            let left = garden.get(&(row, col - 1));
-
-            let no_up = up.is_none() || up.unwrap().plant_type != plant.plant_type;
-            let no_left = left.is_none() || left.unwrap().plant_type != plant.plant_type;
-
-            if no_up && no_left {
-                garden
-                    .entry((row, col))
-                    .and_modify(|p| p.region_id = Some(Uuid::new_v4()));
-            } else if !no_up && no_left {
-                let id = up.unwrap().region_id;
-                garden.entry((row, col)).and_modify(|p| p.region_id = id);
-            } else if no_up && !no_left {
-                let id = left.unwrap().region_id;
-                garden.entry((row, col)).and_modify(|p| p.region_id = id);
-            } else {
-                let left_id = left.unwrap().region_id;
-                let up_id = up.unwrap().region_id;
-
-                if left_id == up_id {
-                    garden
-                        .entry((row, col))
-                        .and_modify(|p| p.region_id = up_id);
-                } else {
-                    let new_id = Uuid::new_v4();
-                    replace_ids(garden, left_id.unwrap(), new_id);
-                    replace_ids(garden, up_id.unwrap(), new_id);
-                    garden
-                        .entry((row, col))
-                        .and_modify(|p| p.region_id = Some(new_id));
+    fn label_regions(&mut self) {
+        for row in 0..self.plants.len() {
+            for col in 0..self.plants[row].len() {
+                if self.labels[row][col] == usize::MAX {
+                    let cells = self.flood((row as i32, col as i32), self.regions.len());
+                    let region = self.describe(self.regions.len(), &cells);
+                    self.regions.push(region);
                 }
             }
         }
     }
-}
 
-fn replace_ids(garden: &mut HashMap<(i32, i32), Plant>, target: Uuid, replacement: Uuid) {
-    for entry in garden.values_mut() {
-        if entry.region_id == Some(target) {
-            entry.region_id = Some(replacement);
+    fn flood(&mut self, start: (i32, i32), id: usize) -> Vec<(i32, i32)> {
+        let plant_type = self.plant(start).unwrap();
+        let mut cells = vec![start];
+        let mut queue = VecDeque::from([start]);
+        self.labels[start.0 as usize][start.1 as usize] = id;
+
+        while let Some(pos) = queue.pop_front() {
+            for direction in DIRECTIONS {
+                let next = (pos.0 + direction.0, pos.1 + direction.1);
+                if self.plant(next) == Some(plant_type)
+                    && self.labels[next.0 as usize][next.1 as usize] == usize::MAX
+                {
+                    self.labels[next.0 as usize][next.1 as usize] = id;
+                    cells.push(next);
+                    queue.push_back(next);
+                }
+            }
         }
+        cells
     }
-}
 
-fn get_price_perimeter(garden: &HashMap<(i32, i32), Plant>) -> usize {
-    let mut regions: HashMap<Uuid, (usize, usize)> = HashMap::new();
-    for plant in garden.values() {
-        regions
-            .entry(plant.region_id.unwrap())
-            .and_modify(|ap| {
-                ap.0 += 1;
-                ap.1 += plant.perimeter;
-            })
-            .or_insert((1, plant.perimeter));
-    }
+    fn describe(&self, id: usize, cells: &[(i32, i32)]) -> Region {
+        let mut perimeter = 0;
+        let mut sides = 0;
+        for &(row, col) in cells {
+            perimeter += DIRECTIONS
+                .iter()
+                .filter(|d| !self.in_region((row + d.0, col + d.1), id))
+                .count();
 
-    regions.values().map(|v| v.0 * v.1).sum::<usize>()
-}
+            // A region has as many sides as it has corners.
+            for (dr, dc) in DIAGONALS {
+                let vertical = self.in_region((row + dr, col), id);
+                let horizontal = self.in_region((row, col + dc), id);
+                let diagonal = self.in_region((row + dr, col + dc), id);
+                if (!vertical && !horizontal) || (vertical && horizontal && !diagonal) {
+                    sides += 1;
+                }
+            }
+        }
+
+        let top_left = (
+            cells.iter().map(|c| c.0).min().unwrap(),
+            cells.iter().map(|c| c.1).min().unwrap(),
+        );
+        let bottom_right = (
+            cells.iter().map(|c| c.0).max().unwrap(),
+            cells.iter().map(|c| c.1).max().unwrap(),
+        );
 
-fn get_price_sides(garden: &HashMap<(i32, i32), Plant>) -> usize {
-    let mut regions = HashSet::new();
-    for plant in garden.values() {
-        regions.insert(plant.region_id.unwrap());
+        Region {
+            id,
+            plant_type: self.plant(cells[0]).unwrap(),
+            area: cells.len(),
+            perimeter,
+            sides,
+            bounding_box: (top_left, bottom_right),
+            holes: self.count_holes(id, top_left, bottom_right),
+        }
     }
 
-    let (maxrow, maxcol) = get_garden_bounds(garden);
-    let mut regions_totals = HashMap::new();
+    /// Counts pockets of other plants fully enclosed by the region. Regions are
+    /// 4-connected, so the pockets have to be 8-connected.
+    fn count_holes(&self, id: usize, top_left: (i32, i32), bottom_right: (i32, i32)) -> usize {
+        // Pad the bounding box by one, so that everything outside of it is connected.
+        let (min_row, min_col) = (top_left.0 - 1, top_left.1 - 1);
+        let height = (bottom_right.0 - min_row + 2) as usize;
+        let width = (bottom_right.1 - min_col + 2) as usize;
+        let mut seen = vec![vec![false; width]; height];
 
-    for region in regions {
-        let mut region_sides = 0;
-        let mut region_area = 0;
-        for row in 0..=maxrow {
-            for col in 0..=maxcol {
-                let plant = garden.get(&(row, col)).unwrap();
-                if plant.region_id != Some(region) {
+        let mut components = 0;
+        for row in 0..height {
+            for col in 0..width {
+                let pos = (row as i32 + min_row, col as i32 + min_col);
+                if seen[row][col] || self.in_region(pos, id) {
                     continue;
                 }
 
-                region_area += 1;
-
-                let ll = garden.get(&(row, col - 1));
-                let lu = garden.get(&(row - 1, col - 1));
-                let uu = garden.get(&(row - 1, col));
-                let ru = garden.get(&(row - 1, col + 1));
-                let rr = garden.get(&(row, col + 1));
-                let dd = garden.get(&(row + 1, col));
-                let ld = garden.get(&(row + 1, col - 1));
-                let has_ll = ll.is_some() && ll.unwrap().region_id == Some(region);
-                let has_lu = lu.is_some() && lu.unwrap().region_id == Some(region);
-                let has_uu = uu.is_some() && uu.unwrap().region_id == Some(region);
-                let has_dd = dd.is_some() && dd.unwrap().region_id == Some(region);
-                let has_ld = ld.is_some() && ld.unwrap().region_id == Some(region);
-                let has_rr = rr.is_some() && rr.unwrap().region_id == Some(region);
-                let has_ru = ru.is_some() && ru.unwrap().region_id == Some(region);
-
-                if (has_ll && !has_lu) || has_uu {} else {region_sides += 1;}
-                if (has_ll && !has_ld) || has_dd {} else {region_sides += 1;}
-                if (has_uu && !has_lu) || has_ll {} else {region_sides += 1;}
-                if (has_uu && !has_ru) || has_rr {} else {region_sides += 1;}
+                components += 1;
+                seen[row][col] = true;
+                let mut queue = VecDeque::from([(row, col)]);
+                while let Some((r, c)) = queue.pop_front() {
+                    for (dr, dc) in DIRECTIONS.iter().chain(DIAGONALS.iter()) {
+                        let (nr, nc) = (r as i32 + dr, c as i32 + dc);
+                        if nr < 0 || nc < 0 || nr >= height as i32 || nc >= width as i32 {
+                            continue;
+                        }
+
+                        let (nr, nc) = (nr as usize, nc as usize);
+                        let npos = (nr as i32 + min_row, nc as i32 + min_col);
+                        if !seen[nr][nc] && !self.in_region(npos, id) {
+                            seen[nr][nc] = true;
+                            queue.push_back((nr, nc));
+                        }
+                    }
+                }
             }
         }
-        regions_totals.insert(region, (region_sides, region_area));
+
+        // The first component found is always the one surrounding the region.
+        components - 1
     }
+}
 
-    regions_totals.values().map(|r| r.0 * r.1).sum()
+fn render_table(regions: &[&Region]) -> String {
+    let mut table = format!(
+        "{:>6} {:>5} {:>6} {:>9} {:>6} {:>6} {:>19}\n",
+        "id", "plant", "area", "perimeter", "sides", "holes", "bounding box"
+    );
+    for region in regions {
+        let ((r0, c0), (r1, c1)) = region.bounding_box;
+        table.push_str(&format!(
+            "{:>6} {:>5} {:>6} {:>9} {:>6} {:>6} {:>19}\n",
+            region.id,
+            region.plant_type,
+            region.area,
+            region.perimeter,
+            region.sides,
+            region.holes,
+            format!("({r0},{c0})-({r1},{c1})"),
+        ));
+    }
+    table
 }
 
 fn main() {
     let data = include_str!("../input");
 
-    let mut garden = build_garden(data);
-    find_perimeters(&mut garden);
-    group_regions(&mut garden);
+    let garden = Garden::build(data);
 
-    let part_1 = get_price_perimeter(&garden);
+    let part_1 = garden
+        .regions
+        .iter()
+        .map(|r| r.area * r.perimeter)
+        .sum::<usize>();
     println!("Part 1: {part_1}");
 
-    let part_2 = get_price_sides(&garden);
+    let part_2 = garden
+        .regions
+        .iter()
+        .map(|r| r.area * r.sides)
+        .sum::<usize>();
     println!("Part 2: {part_2}");
+
+    if std::env::args().any(|arg| arg == "--table") {
+        let mut most_expensive = garden.regions.iter().collect::<Vec<&Region>>();
+        most_expensive.sort_by_key(|r| std::cmp::Reverse(r.area * r.perimeter));
+        println!(
+            "\n{}",
+            render_table(&most_expensive[..most_expensive.len().min(10)])
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LARGER_EXAMPLE: &str = "\
+RRRRIICCFF
+RRRRIICCCF
+VVRRRCCFFF
+VVRCCCJFFF
+VVVVCJJCFE
+VVIVCCJJEE
+VVIIICJJEE
+MIIIIIJJEE
+MIIISIJEEE
+MMMISSJEEE";
+
+    const AB_EXAMPLE: &str = "\
+AAAAAA
+AAABBA
+AAABBA
+ABBAAA
+ABBAAA
+AAAAAA";
+
+    fn prices(garden: &Garden) -> (usize, usize) {
+        garden.regions.iter().fold((0, 0), |(fences, bulk), r| {
+            (fences + r.area * r.perimeter, bulk + r.area * r.sides)
+        })
+    }
+
+    #[test]
+    fn examples() {
+        assert_eq!(prices(&Garden::build(LARGER_EXAMPLE)), (1930, 1206));
+        assert_eq!(
+            prices(&Garden::build("EEEEE\nEXXXX\nEEEEE\nEXXXX\nEEEEE")).1,
+            236
+        );
+        assert_eq!(prices(&Garden::build(AB_EXAMPLE)).1, 368);
+    }
+
+    #[test]
+    fn describes_regions() {
+        let garden = Garden::build(AB_EXAMPLE);
+        assert_eq!(garden.regions.len(), 3);
+
+        let a = &garden.regions[0];
+        assert_eq!((a.plant_type, a.area, a.sides), ('A', 28, 12));
+        assert_eq!(a.bounding_box, ((0, 0), (5, 5)));
+        // The two B blocks touch diagonally, which makes them a single pocket.
+        assert_eq!(a.holes, 1);
+
+        let b = &garden.regions[1];
+        assert_eq!((b.plant_type, b.area, b.perimeter, b.sides), ('B', 4, 8, 4));
+        assert_eq!(b.bounding_box, ((1, 3), (2, 4)));
+        assert_eq!(b.holes, 0);
+
+        // One O region around four enclosed X plants.
+        let garden = Garden::build("OOOOO\nOXOXO\nOOOOO\nOXOXO\nOOOOO");
+        assert_eq!(garden.regions[0].holes, 4);
+        assert_eq!(garden.regions[0].sides, 20);
+    }
 }
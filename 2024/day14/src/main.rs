@@ -63,6 +63,8 @@ impl Robot {
     }
 }
 
+// The robots draw a Christmas tree rather than block letters, so unlike the
+// CRT of 2022/day10 there is nothing for the shared OCR module to read here.
 fn render_robots(robots: &Vec<Robot>, width: i32, height: i32) {
     let mut positions = HashSet::new();
     for robot in robots {
@@ -1,218 +1,186 @@
 use std::collections::{HashMap, HashSet, VecDeque};
 
-type Map = HashMap<(i32, i32), char>;
-
-fn make_small_warehouse(data: &str) -> (Map, (i32, i32)) {
-    let mut warehouse = HashMap::new();
-    let mut robot = (0, 0);
-
-    for (row, line) in data.lines().enumerate() {
-        for (col, chr) in line.chars().enumerate() {
-            if chr == '@' {
-                warehouse.insert((row as i32, col as i32), '.');
-                robot = (row as i32, col as i32);
-            } else {
-                warehouse.insert((row as i32, col as i32), chr);
-            }
-        }
+/// Object that moves as a whole when pushed, occupying any number of cells.
+struct Object {
+    cells: Vec<((i32, i32), char)>,
+}
+
+impl Object {
+    fn gps(&self) -> i32 {
+        let row = self.cells.iter().map(|c| c.0 .0).min().unwrap();
+        let col = self.cells.iter().map(|c| c.0 .1).min().unwrap();
+        row * 100 + col
     }
+}
 
-    (warehouse, robot)
+struct Warehouse {
+    height: i32,
+    width: i32,
+    walls: HashSet<(i32, i32)>,
+    objects: Vec<Object>,
+    occupied: HashMap<(i32, i32), usize>,
+    robot: (i32, i32),
 }
 
-fn make_big_warehouse(data: &str) -> (Map, (i32, i32)) {
-    let mut warehouse = HashMap::new();
-    let mut robot = (0, 0);
-    let mut coord = (0, 0);
-
-    for line in data.lines() {
-        for col in line.chars() {
-            match col {
-                '#' => {
-                    warehouse.insert((coord.0, coord.1), '#');
-                    warehouse.insert((coord.0, coord.1 + 1), '#');
-                }
-                'O' => {
-                    warehouse.insert((coord.0, coord.1), '[');
-                    warehouse.insert((coord.0, coord.1 + 1), ']');
-                }
-                '.' => {
-                    warehouse.insert((coord.0, coord.1), '.');
-                    warehouse.insert((coord.0, coord.1 + 1), '.');
+impl Warehouse {
+    /// Builds a warehouse, with every map tile turned into the block of glyphs
+    /// returned by `expand`. Every non-`.` glyph an `O` expands into belongs to the
+    /// same object.
+    fn build(data: &str, expand: fn(char) -> Vec<&'static str>) -> Self {
+        let mut walls = HashSet::new();
+        let mut objects = Vec::new();
+        let mut robot = (0, 0);
+        let (mut height, mut width) = (0, 0);
+
+        for (row, line) in data.lines().enumerate() {
+            for (col, chr) in line.chars().enumerate() {
+                let block = expand(chr);
+                let block_height = block.len() as i32;
+                let block_width = block[0].len() as i32;
+                let origin = (row as i32 * block_height, col as i32 * block_width);
+                height = height.max(origin.0 + block_height);
+                width = width.max(origin.1 + block_width);
+
+                let mut cells = Vec::new();
+                for (dr, glyphs) in block.iter().enumerate() {
+                    for (dc, glyph) in glyphs.chars().enumerate() {
+                        let pos = (origin.0 + dr as i32, origin.1 + dc as i32);
+                        match glyph {
+                            '#' => {
+                                walls.insert(pos);
+                            }
+                            '@' => robot = pos,
+                            '.' => {}
+                            _ => cells.push((pos, glyph)),
+                        }
+                    }
                 }
-                '@' => {
-                    robot = coord;
-                    warehouse.insert((coord.0, coord.1), '.');
-                    warehouse.insert((coord.0, coord.1 + 1), '.');
+
+                if !cells.is_empty() {
+                    objects.push(Object { cells });
                 }
-                _ => unreachable!(),
             }
-
-            coord = (coord.0, coord.1 + 2);
         }
-        coord = (coord.0 + 1, 0);
-    }
 
-    (warehouse, robot)
-}
-
-fn move_robot_and_small_boxes(robot: &mut (i32, i32), warehouse: &mut Map, movement: char) {
-    let direction = match movement {
-        '>' => (0, 1),
-        'v' => (1, 0),
-        '<' => (0, -1),
-        '^' => (-1, 0),
-        '\n' => return,
-        _ => unreachable!(),
-    };
-
-    let mut to_move = Vec::new();
-    let mut total_direction = direction;
-    while let Some(element) =
-        warehouse.get(&(robot.0 + total_direction.0, robot.1 + total_direction.1))
-    {
-        match element {
-            '#' => return,
-            '.' => break,
-            'O' => {
-                to_move.push((robot.0 + total_direction.0, robot.1 + total_direction.1));
+        let mut occupied = HashMap::new();
+        for (id, object) in objects.iter().enumerate() {
+            for (pos, _) in &object.cells {
+                occupied.insert(*pos, id);
             }
-            _ => unreachable!(),
         }
-        total_direction = (
-            total_direction.0 + direction.0,
-            total_direction.1 + direction.1,
-        );
-    }
 
-    *robot = (robot.0 + direction.0, robot.1 + direction.1);
-    for box_ in to_move.iter().rev() {
-        warehouse.insert(*box_, '.');
-        warehouse.insert((box_.0 + direction.0, box_.1 + direction.1), 'O');
+        Self {
+            height,
+            width,
+            walls,
+            objects,
+            occupied,
+            robot,
+        }
     }
-}
 
-fn move_robot_and_big_boxes(robot: &mut (i32, i32), warehouse: &mut Map, movement: char) {
-    let direction = match movement {
-        '>' => (0, 1),
-        'v' => (1, 0),
-        '<' => (0, -1),
-        '^' => (-1, 0),
-        '\n' => return,
-        _ => unreachable!(),
-    };
+    /// Moves the robot, along with every object it transitively pushes, or nothing
+    /// at all if any of them would end up in a wall.
+    fn push(&mut self, direction: (i32, i32)) -> bool {
+        let mut pushed = Vec::new();
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::from([(self.robot.0 + direction.0, self.robot.1 + direction.1)]);
 
-    match movement {
-        '<' | '>' => make_left_right_move(robot, warehouse, direction),
-        '^' | 'v' => make_up_down_move(robot, warehouse, direction),
-        _ => unreachable!(),
-    }
-}
+        while let Some(pos) = queue.pop_front() {
+            if self.walls.contains(&pos) {
+                return false;
+            }
 
-fn make_left_right_move(robot: &mut (i32, i32), warehouse: &mut Map, direction: (i32, i32)) {
-    let mut to_move = Vec::new();
-    let mut total_direction = direction;
-    while let Some(element) =
-        warehouse.get(&(robot.0 + total_direction.0, robot.1 + total_direction.1))
-    {
-        match element {
-            '#' => return,
-            '.' => break,
-            '[' | ']' => {
-                to_move.push((
-                    robot.0 + total_direction.0,
-                    robot.1 + total_direction.1,
-                    *element,
-                ));
+            if let Some(id) = self.occupied.get(&pos) {
+                if seen.insert(*id) {
+                    pushed.push(*id);
+                    for (cell, _) in &self.objects[*id].cells {
+                        queue.push_back((cell.0 + direction.0, cell.1 + direction.1));
+                    }
+                }
             }
-            _ => unreachable!(),
         }
-        total_direction = (
-            total_direction.0 + direction.0,
-            total_direction.1 + direction.1,
-        );
-    }
-
-    *robot = (robot.0 + direction.0, robot.1 + direction.1);
-    for box_ in to_move.iter().rev() {
-        warehouse.insert((box_.0, box_.1), '.');
-        warehouse.insert((box_.0 + direction.0, box_.1 + direction.1), box_.2);
-    }
-}
 
-fn make_up_down_move(robot: &mut (i32, i32), warehouse: &mut Map, direction: (i32, i32)) {
-    let mut queue = VecDeque::new();
-    let mut seen = HashSet::new();
-    queue.push_back((robot.0 + direction.0, robot.1 + direction.1));
-
-    while let Some(pos) = queue.pop_front() {
-        match warehouse.get(&pos).unwrap() {
-            '#' => return,
-            '.' => {}
-            '[' => {
-                if !seen.contains(&(pos.0, pos.1 + 1)) {
-                    queue.push_back((pos.0, pos.1 + 1));
-                }
-                queue.push_back((pos.0 + direction.0, pos.1 + direction.1));
-                seen.insert(pos);
+        for id in &pushed {
+            for (cell, _) in &self.objects[*id].cells {
+                self.occupied.remove(cell);
             }
-            ']' => {
-                if !seen.contains(&(pos.0, pos.1 - 1)) {
-                    queue.push_back((pos.0, pos.1 - 1));
-                }
-                queue.push_back((pos.0 + direction.0, pos.1 + direction.1));
-                seen.insert(pos);
+        }
+        for id in &pushed {
+            for (cell, _) in self.objects[*id].cells.iter_mut() {
+                *cell = (cell.0 + direction.0, cell.1 + direction.1);
+                self.occupied.insert(*cell, *id);
             }
-            _ => unreachable!(),
         }
-    }
 
-    let mut to_move = seen.iter().collect::<Vec<&(i32, i32)>>();
-    to_move.sort_by_key(|k| k.0);
-    if direction == (1, 0) {
-        to_move.reverse();
+        self.robot = (self.robot.0 + direction.0, self.robot.1 + direction.1);
+        true
     }
 
-    for box_part_pos in to_move {
-        let box_part = *warehouse.get(box_part_pos).unwrap();
-        warehouse.insert(*box_part_pos, '.');
-        warehouse.insert(
-            (box_part_pos.0 + direction.0, box_part_pos.1 + direction.1),
-            box_part,
-        );
-    }
+    /// Follows all movements, printing the warehouse after each one when replaying.
+    fn run(&mut self, movements: &str, replay: bool) {
+        if replay {
+            println!("Initial state:\n{}", self.render());
+        }
 
-    *robot = (robot.0 + direction.0, robot.1 + direction.1);
-}
+        for movement in movements.chars() {
+            let direction = match movement {
+                '>' => (0, 1),
+                'v' => (1, 0),
+                '<' => (0, -1),
+                '^' => (-1, 0),
+                '\n' => continue,
+                _ => unreachable!(),
+            };
+            self.push(direction);
 
-fn calc_coordinates(warehouse: &Map, box_: char) -> i32 {
-    let mut total = 0;
-    for (coord, item) in warehouse {
-        if item == &box_ {
-            total += coord.0 * 100 + coord.1;
+            if replay {
+                println!("Move {movement}:\n{}", self.render());
+            }
         }
     }
-    total
-}
 
-fn move_around_small_warehouse(data: &[&str]) -> i32 {
-    let (mut warehouse, mut robot) = make_small_warehouse(data[0]);
+    fn render(&self) -> String {
+        let mut canvas = vec![vec!['.'; self.width as usize]; self.height as usize];
+        for wall in &self.walls {
+            canvas[wall.0 as usize][wall.1 as usize] = '#';
+        }
+        for object in &self.objects {
+            for (pos, glyph) in &object.cells {
+                canvas[pos.0 as usize][pos.1 as usize] = *glyph;
+            }
+        }
+        canvas[self.robot.0 as usize][self.robot.1 as usize] = '@';
 
-    for movement in data[1].chars() {
-        move_robot_and_small_boxes(&mut robot, &mut warehouse, movement);
+        canvas
+            .into_iter()
+            .map(|row| row.into_iter().collect::<String>() + "\n")
+            .collect()
     }
 
-    calc_coordinates(&warehouse, 'O')
+    fn gps_total(&self) -> i32 {
+        self.objects.iter().map(Object::gps).sum()
+    }
 }
 
-fn move_around_big_warehouse(data: &[&str]) -> i32 {
-    let (mut warehouse, mut robot) = make_big_warehouse(data[0]);
-
-    for movement in data[1].chars() {
-        move_robot_and_big_boxes(&mut robot, &mut warehouse, movement);
+fn small_tiles(chr: char) -> Vec<&'static str> {
+    match chr {
+        '#' => vec!["#"],
+        'O' => vec!["O"],
+        '.' => vec!["."],
+        '@' => vec!["@"],
+        _ => unreachable!(),
     }
+}
 
-    calc_coordinates(&warehouse, '[')
+fn big_tiles(chr: char) -> Vec<&'static str> {
+    match chr {
+        '#' => vec!["##"],
+        'O' => vec!["[]"],
+        '.' => vec![".."],
+        '@' => vec!["@."],
+        _ => unreachable!(),
+    }
 }
 
 fn main() {
@@ -220,9 +188,73 @@ fn main() {
         .split("\n\n")
         .collect::<Vec<&str>>();
 
-    let part_1 = move_around_small_warehouse(&data);
-    println!("{part_1}");
+    let replay = std::env::args().any(|arg| arg == "--replay");
+    let mut small = Warehouse::build(data[0], small_tiles);
+    small.run(data[1], replay);
+    println!("Part 1: {}", small.gps_total());
+
+    let mut big = Warehouse::build(data[0], big_tiles);
+    big.run(data[1], replay);
+    println!("Part 2: {}", big.gps_total());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SMALLER_EXAMPLE: &str = "\
+########
+#..O.O.#
+##@.O..#
+#...O..#
+#.#.O..#
+#...O..#
+#......#
+########
+
+<^^>>>vv<v>>v<<";
+
+    fn build(data: &str, expand: fn(char) -> Vec<&'static str>) -> (Warehouse, &str) {
+        let (map, movements) = data.split_once("\n\n").unwrap();
+        (Warehouse::build(map, expand), movements)
+    }
+
+    #[test]
+    fn examples() {
+        let (mut warehouse, movements) = build(SMALLER_EXAMPLE, small_tiles);
+        warehouse.run(movements, false);
+        assert_eq!(
+            warehouse.render(),
+            "########\n#....OO#\n##.....#\n#.....O#\n#.#O@..#\n#...O..#\n#...O..#\n########\n"
+        );
+        assert_eq!(warehouse.gps_total(), 2028);
+
+        let (mut warehouse, movements) = build(include_str!("../test-input"), big_tiles);
+        warehouse.run(movements, false);
+        assert_eq!(
+            warehouse.render(),
+            "##############\n##...[].##..##\n##...@.[]...##\n##....[]....##\n\
+             ##..........##\n##..........##\n##############\n"
+        );
+        assert_eq!(warehouse.gps_total(), 618);
+    }
+
+    #[test]
+    fn blocked_chains_stay_put() {
+        let map = "#######\n#.#...#\n#.O...#\n#..O@.#\n#.....#\n#######";
+        let mut warehouse = Warehouse::build(map, big_tiles);
+        // Shift the lower box half a tile, then get under it.
+        warehouse.run("<v<", false);
+        let before = warehouse.render();
+        assert_eq!(
+            before,
+            "##############\n##..##......##\n##..[]......##\n##...[].....##\n\
+             ##....@.....##\n##############\n"
+        );
 
-    let part_2 = move_around_big_warehouse(&data);
-    println!("{part_2}");
+        // The lower box pushes the upper one, which is stuck under the wall.
+        assert!(!warehouse.push((-1, 0)));
+        assert_eq!(warehouse.render(), before);
+        assert!(warehouse.push((0, 1)));
+    }
 }
@@ -8,88 +8,78 @@ type Map = HashMap<(i32, i32), char>;
 // Up, right, down, left
 const HEADINGS: &[(i32, i32)] = &[(-1, 0), (0, 1), (1, 0), (0, -1)];
 
+type State = ((i32, i32), usize);
+
 #[derive(Debug, PartialEq, Eq)]
 struct Node {
     position: (i32, i32),
     heading: usize,
     total_cost: usize,
-    visited: HashSet<(i32, i32)>,
 }
 
 impl Node {
-    fn new(
-        position: (i32, i32),
-        heading: usize,
-        total_cost: usize,
-        mut visited: HashSet<(i32, i32)>,
-    ) -> Self {
-        visited.insert(position);
+    fn new(position: (i32, i32), heading: usize, total_cost: usize) -> Self {
         Self {
             position,
             heading,
             total_cost,
-            visited,
         }
     }
 
+    fn state(&self) -> State {
+        (self.position, self.heading)
+    }
+
     fn next_move(&self, map: &Map) -> Vec<Self> {
-        let mut next_moves = Vec::new();
         let direction = HEADINGS[self.heading];
-        let neighbor = map
-            .get(&(self.position.0 + direction.0, self.position.1 + direction.1))
-            .unwrap();
+        let newpos = (self.position.0 + direction.0, self.position.1 + direction.1);
         let turn_left = (self.heading + 3) % 4;
         let turn_right = (self.heading + 1) % 4;
 
-        if neighbor == &'#' {
-            next_moves.push(Self::new(
-                self.position,
-                turn_left,
-                self.total_cost + 1000,
-                self.visited.clone(),
-            ));
-            next_moves.push(Self::new(
-                self.position,
-                turn_right,
-                self.total_cost + 1000,
-                self.visited.clone(),
-            ));
-        } else {
-            let newpos = (self.position.0 + direction.0, self.position.1 + direction.1);
-            next_moves.push(Self::new(
-                newpos,
-                self.heading,
-                self.total_cost + 1,
-                self.visited.clone(),
-            ));
-
-            let maybe_left = HEADINGS[turn_left];
-            if let Some('.') = map.get(&(
-                self.position.0 + maybe_left.0,
-                self.position.1 + maybe_left.1,
-            )) {
-                next_moves.push(Node::new(
-                    self.position,
-                    turn_left,
-                    self.total_cost + 1000,
-                    self.visited.clone(),
-                ));
-            }
+        let mut next_moves = vec![
+            Self::new(self.position, turn_left, self.total_cost + 1000),
+            Self::new(self.position, turn_right, self.total_cost + 1000),
+        ];
+        if map.get(&newpos) == Some(&'.') {
+            next_moves.push(Self::new(newpos, self.heading, self.total_cost + 1));
+        }
+        next_moves
+    }
+}
+
+/// Every optimal path from start to end, stored as a DAG of predecessors.
+struct OptimalPaths {
+    cost: usize,
+    ends: Vec<State>,
+    predecessors: HashMap<State, Vec<State>>,
+    // States in the order Dijkstra settled them, so predecessors always come first.
+    settled: Vec<State>,
+}
 
-            let maybe_right = HEADINGS[turn_right];
-            if let Some('.') = map.get(&(
-                self.position.0 + maybe_right.0,
-                self.position.1 + maybe_right.1,
-            )) {
-                next_moves.push(Node::new(
-                    self.position,
-                    turn_right,
-                    self.total_cost + 1000,
-                    self.visited.clone(),
-                ));
+impl OptimalPaths {
+    fn tiles(&self) -> HashSet<(i32, i32)> {
+        let mut seen = HashSet::new();
+        let mut stack = self.ends.clone();
+        while let Some(state) = stack.pop() {
+            if seen.insert(state) {
+                if let Some(preds) = self.predecessors.get(&state) {
+                    stack.extend(preds.iter().copied());
+                }
             }
         }
-        next_moves
+        seen.into_iter().map(|(position, _)| position).collect()
+    }
+
+    fn count(&self) -> u128 {
+        let mut paths: HashMap<State, u128> = HashMap::new();
+        for state in &self.settled {
+            let count = match self.predecessors.get(state) {
+                Some(preds) => preds.iter().map(|p| paths[p]).sum(),
+                None => 1,
+            };
+            paths.insert(*state, count);
+        }
+        self.ends.iter().map(|e| paths[e]).sum()
     }
 }
 
@@ -126,30 +116,73 @@ impl Ord for Node {
     }
 }
 
-fn traverse(maze: &Map, start: (i32, i32), end: (i32, i32)) -> Vec<Node> {
-    let mut seen = HashMap::new();
+fn traverse(maze: &Map, start: (i32, i32), end: (i32, i32)) -> OptimalPaths {
+    let mut costs = HashMap::new();
+    let mut predecessors: HashMap<State, Vec<State>> = HashMap::new();
+    let mut settled = Vec::new();
+    let mut settled_set = HashSet::new();
+    let mut ends = Vec::new();
+    let mut best = usize::MAX;
+
     let mut queue = BinaryHeap::new();
-    let mut end_nodes = Vec::new();
-    queue.push(Reverse(Node::new(start, 1, 0, HashSet::new())));
+    let start_node = Node::new(start, 1, 0);
+    costs.insert(start_node.state(), 0);
+    queue.push(Reverse(start_node));
 
     while let Some(Reverse(node)) = queue.pop() {
-        if let Some(cost) = seen.get(&(node.position, node.heading)) {
-            if cost < &node.total_cost {
-                continue;
-            }
+        if node.total_cost > best {
+            break;
+        }
+        if !settled_set.insert(node.state()) {
+            continue;
         }
-        seen.insert((node.position, node.heading), node.total_cost);
+        settled.push(node.state());
 
         if node.position == end {
-            end_nodes.push(node);
+            best = node.total_cost;
+            ends.push(node.state());
             continue;
         }
 
         for neighbor in node.next_move(maze) {
-            queue.push(Reverse(neighbor));
+            let known = costs.get(&neighbor.state()).copied().unwrap_or(usize::MAX);
+            if neighbor.total_cost < known {
+                costs.insert(neighbor.state(), neighbor.total_cost);
+                predecessors.insert(neighbor.state(), vec![node.state()]);
+                queue.push(Reverse(neighbor));
+            } else if neighbor.total_cost == known {
+                predecessors
+                    .entry(neighbor.state())
+                    .or_default()
+                    .push(node.state());
+            }
         }
     }
-    end_nodes
+
+    OptimalPaths {
+        cost: best,
+        ends,
+        predecessors,
+        settled,
+    }
+}
+
+fn render_overlay(maze: &Map, tiles: &HashSet<(i32, i32)>) -> String {
+    let maxrow = maze.keys().map(|k| k.0).max().unwrap();
+    let maxcol = maze.keys().map(|k| k.1).max().unwrap();
+
+    let mut rendered = String::new();
+    for row in 0..=maxrow {
+        for col in 0..=maxcol {
+            if tiles.contains(&(row, col)) {
+                rendered.push('O');
+            } else {
+                rendered.push(maze[&(row, col)]);
+            }
+        }
+        rendered.push('\n');
+    }
+    rendered
 }
 
 fn main() {
@@ -158,14 +191,35 @@ fn main() {
     let (maze, start, end) = build_maze(data);
 
     let paths = traverse(&maze, start, end);
-    let part_1 = paths.iter().map(|p| p.total_cost).min().unwrap();
-    println!("Part 1: {part_1}");
+    println!("Part 1: {}", paths.cost);
 
-    let mut seats = HashSet::new();
-    for p in paths.into_iter().filter(|p| p.total_cost == part_1) {
-        for v in p.visited {
-            seats.insert(v);
-        }
-    }
+    let seats = paths.tiles();
     println!("Part 2: {}", seats.len());
+
+    if std::env::args().any(|arg| arg == "--render") {
+        println!("\n{} optimal paths", paths.count());
+        print!("{}", render_overlay(&maze, &seats));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solve(data: &str) -> (usize, usize, u128) {
+        let (maze, start, end) = build_maze(data);
+        let paths = traverse(&maze, start, end);
+        let seats = paths.tiles();
+        assert_eq!(
+            render_overlay(&maze, &seats).matches('O').count(),
+            seats.len()
+        );
+        (paths.cost, seats.len(), paths.count())
+    }
+
+    #[test]
+    fn examples() {
+        assert_eq!(solve(include_str!("../test-input")), (7036, 45, 3));
+        assert_eq!(solve(include_str!("../test-input2")), (11048, 64, 2));
+    }
 }
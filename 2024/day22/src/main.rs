@@ -1,3 +1,6 @@
+// Price changes range from -9 to 9, so a sequence of four is a base-19 number.
+const SEQUENCES: usize = 19 * 19 * 19 * 19;
+
 fn mix_and_prune(mut secret_number: usize) -> usize {
     secret_number = (secret_number * 64) ^ secret_number;
     secret_number %= 16777216;
@@ -16,54 +19,119 @@ fn find_secret_number(initial_number: usize) -> usize {
     secret_number
 }
 
-fn get_sequence(initial_number: usize) -> Vec<Vec<i32>> {
+/// Calls `sell` with the index and price of the first sale each sequence of
+/// price changes would trigger.
+fn for_each_sale(initial_number: usize, mut sell: impl FnMut(usize, usize)) {
     let mut secret_number = initial_number;
-    let mut seq = Vec::new();
-    seq.push(initial_number % 10);
+    let mut price = initial_number % 10;
+    let mut index = 0;
 
-    for _ in 0..2000 {
+    for n in 0..2000 {
         secret_number = mix_and_prune(secret_number);
-        seq.push(secret_number % 10);
+        let next_price = secret_number % 10;
+        index = (index * 19 + next_price + 9 - price) % SEQUENCES;
+        price = next_price;
+
+        if n >= 3 {
+            sell(index, price);
+        }
     }
+}
 
-    let diffs = seq
-        .windows(2)
-        .map(|elems| elems[1] as i32 - elems[0] as i32)
-        .collect::<Vec<i32>>();
-    diffs
-        .windows(4)
-        .map(|v| v.to_vec())
-        .collect::<Vec<Vec<i32>>>()
+fn encode_sequence(sequence: [i32; 4]) -> usize {
+    sequence
+        .iter()
+        .fold(0, |index, change| index * 19 + (change + 9) as usize)
 }
 
-fn try_sequence(initial_number: usize, sequence: &[i32]) -> usize {
-    let mut secret_number = initial_number;
-    let mut seq = Vec::new();
-    seq.push(initial_number % 10);
+fn decode_sequence(mut index: usize) -> [i32; 4] {
+    let mut sequence = [0; 4];
+    for change in sequence.iter_mut().rev() {
+        *change = (index % 19) as i32 - 9;
+        index /= 19;
+    }
+    sequence
+}
 
-    for _ in 0..2000 {
-        secret_number = mix_and_prune(secret_number);
-        seq.push(secret_number % 10);
+/// Bananas each sequence of price changes would earn across the given buyers.
+fn accumulate_bananas(buyers: &[usize]) -> Vec<usize> {
+    let mut bananas = vec![0; SEQUENCES];
+    let mut seen = vec![0u64; SEQUENCES.div_ceil(64)];
+
+    for buyer in buyers {
+        seen.fill(0);
+        for_each_sale(*buyer, |index, price| {
+            let (word, bit) = (index / 64, 1 << (index % 64));
+            // A buyer only ever sells the first time a sequence shows up.
+            if seen[word] & bit == 0 {
+                seen[word] |= bit;
+                bananas[index] += price;
+            }
+        });
     }
+    bananas
+}
+
+/// The price each buyer sells at for a sequence of price changes.
+fn sales(buyers: &[usize], sequence: [i32; 4]) -> Vec<Option<usize>> {
+    let index = encode_sequence(sequence);
+    buyers
+        .iter()
+        .map(|buyer| {
+            let mut sale = None;
+            for_each_sale(*buyer, |i, price| {
+                if i == index && sale.is_none() {
+                    sale = Some(price);
+                }
+            });
+            sale
+        })
+        .collect()
+}
+
+struct BestSequence {
+    sequence: [i32; 4],
+    bananas: usize,
+    // The price each buyer sold at, `None` if the sequence never showed up.
+    sales: Vec<Option<usize>>,
+}
 
-    let diffs = seq
-        .windows(2)
-        .map(|elems| elems[1] as i32 - elems[0] as i32)
-        .collect::<Vec<i32>>();
+fn find_best_sequence(buyers: &[usize], parallel: bool) -> BestSequence {
+    let bananas = if parallel {
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        std::thread::scope(|scope| {
+            let handles = buyers
+                .chunks(buyers.len().div_ceil(threads).max(1))
+                .map(|chunk| scope.spawn(move || accumulate_bananas(chunk)))
+                .collect::<Vec<_>>();
 
-    let mut idx = 4;
-    for w in diffs.windows(4) {
-        if w == sequence {
-            return seq[idx];
-        }
-        idx += 1;
+            let mut bananas = vec![0; SEQUENCES];
+            for handle in handles {
+                for (total, partial) in bananas.iter_mut().zip(handle.join().unwrap()) {
+                    *total += partial;
+                }
+            }
+            bananas
+        })
+    } else {
+        accumulate_bananas(buyers)
+    };
+
+    let (index, best) = bananas
+        .iter()
+        .enumerate()
+        .max_by_key(|(_, bananas)| **bananas)
+        .unwrap();
+
+    let sequence = decode_sequence(index);
+    BestSequence {
+        sequence,
+        bananas: *best,
+        sales: sales(buyers, sequence),
     }
-    0
 }
 
 fn main() {
-    use std::collections::HashSet;
-
     let data = include_str!("../input")
         .lines()
         .map(|l| l.parse::<usize>().unwrap())
@@ -75,23 +143,40 @@ fn main() {
         .sum::<usize>();
     println!("Part 1: {part_1}");
 
-    let mut possible_sequences = HashSet::new();
-    data.iter().for_each(|num| {
-        let sequence = get_sequence(*num);
-        for entry in sequence {
-            possible_sequences.insert(entry);
-        }
-    });
+    let best = find_best_sequence(&data, true);
+    println!("Part 2: {}", best.bananas);
+
+    if std::env::args().any(|arg| arg == "--sales") {
+        let buying = best.sales.iter().filter(|s| s.is_some()).count();
+        println!(
+            "\nBest sequence {:?} sells to {buying} of {} buyers",
+            best.sequence,
+            best.sales.len()
+        );
+    }
+}
 
-    let mut best_bananas = 0;
-    for sequence in possible_sequences {
-        let bananas = data
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn example_secret_numbers() {
+        let total = [1, 10, 100, 2024]
             .iter()
-            .map(|num| try_sequence(*num, &sequence))
+            .map(|num| find_secret_number(*num))
             .sum::<usize>();
-        if bananas > best_bananas {
-            best_bananas = bananas;
+        assert_eq!(total, 37327623);
+    }
+
+    #[test]
+    fn example_best_sequence() {
+        let buyers = [1, 2, 3, 2024];
+        for parallel in [true, false] {
+            let best = find_best_sequence(&buyers, parallel);
+            assert_eq!(best.bananas, 23);
+            assert_eq!(best.sequence, [-2, 1, -1, 3]);
+            assert_eq!(best.sales, vec![Some(7), Some(7), None, Some(9)]);
         }
     }
-    println!("Part 2: {best_bananas}");
 }
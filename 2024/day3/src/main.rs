@@ -1,9 +1,147 @@
-use regex::Regex;
+use std::fmt;
+
+type Handler = fn(&mut Interpreter, &[i32]) -> bool;
+
+struct Instruction {
+    name: String,
+    arity: usize,
+    handler: Handler,
+}
+
+struct InstructionSet(Vec<Instruction>);
+
+impl InstructionSet {
+    fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    fn standard() -> Self {
+        let mut set = Self::new();
+        set.define("mul", 2, |interpreter, args| {
+            interpreter.add(args[0], args[1])
+        });
+        set.define("do", 0, |interpreter, _| interpreter.do_());
+        set.define("don't", 0, |interpreter, _| interpreter.dont());
+        set
+    }
+
+    /// Registers `name(a,b,...)` with `arity` arguments of 1-3 digits each. Handler
+    /// returns whether the instruction had any effect on the interpreter.
+    fn define(&mut self, name: &str, arity: usize, handler: Handler) {
+        self.0.push(Instruction {
+            name: name.to_string(),
+            arity,
+            handler,
+        });
+    }
+}
+
+struct Token {
+    offset: usize,
+    instruction: usize,
+    args: Vec<i32>,
+}
+
+struct Tokenizer<'a> {
+    program: &'a [u8],
+    instructions: &'a InstructionSet,
+    pos: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn new(program: &'a str, instructions: &'a InstructionSet) -> Self {
+        Self {
+            program: program.as_bytes(),
+            instructions,
+            pos: 0,
+        }
+    }
+
+    fn expect(&self, pos: usize, byte: u8) -> Option<usize> {
+        (self.program.get(pos) == Some(&byte)).then_some(pos + 1)
+    }
+
+    fn number(&self, pos: usize) -> Option<(i32, usize)> {
+        let digits = self.program[pos..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        if !(1..=3).contains(&digits) {
+            return None;
+        }
+
+        let value = self.program[pos..pos + digits]
+            .iter()
+            .fold(0, |acc, b| acc * 10 + (b - b'0') as i32);
+        Some((value, pos + digits))
+    }
+
+    fn parse(&self, instruction: &Instruction) -> Option<(Vec<i32>, usize)> {
+        if !self.program[self.pos..].starts_with(instruction.name.as_bytes()) {
+            return None;
+        }
+
+        let mut pos = self.expect(self.pos + instruction.name.len(), b'(')?;
+        let mut args = Vec::with_capacity(instruction.arity);
+        for idx in 0..instruction.arity {
+            if idx > 0 {
+                pos = self.expect(pos, b',')?;
+            }
+            let (value, next) = self.number(pos)?;
+            args.push(value);
+            pos = next;
+        }
+
+        let end = self.expect(pos, b')')?;
+        Some((args, end))
+    }
+}
+
+impl Iterator for Tokenizer<'_> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pos < self.program.len() {
+            for (idx, instruction) in self.instructions.0.iter().enumerate() {
+                if let Some((args, end)) = self.parse(instruction) {
+                    let offset = self.pos;
+                    self.pos = end;
+                    return Some(Token {
+                        offset,
+                        instruction: idx,
+                        args,
+                    });
+                }
+            }
+            self.pos += 1;
+        }
+        None
+    }
+}
+
+struct Executed {
+    offset: usize,
+    name: String,
+    args: Vec<i32>,
+}
+
+impl fmt::Display for Executed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let args = self
+            .args
+            .iter()
+            .map(|a| a.to_string())
+            .collect::<Vec<String>>()
+            .join(",");
+        write!(f, "{:>6}: {}({})", self.offset, self.name, args)
+    }
+}
 
 struct Interpreter {
     total: i32,
     conditionals_enabled: bool,
     instructions_enabled: bool,
+    trace: Vec<Executed>,
 }
 
 impl Interpreter {
@@ -12,6 +150,7 @@ impl Interpreter {
             total: 0,
             conditionals_enabled: true,
             instructions_enabled: true,
+            trace: Vec::new(),
         }
     }
 
@@ -20,52 +159,148 @@ impl Interpreter {
             total: 0,
             conditionals_enabled: false,
             instructions_enabled: true,
+            trace: Vec::new(),
         }
     }
 
-    fn do_(&mut self) {
+    fn do_(&mut self) -> bool {
         if self.conditionals_enabled {
             self.instructions_enabled = true;
         }
+        self.conditionals_enabled
     }
 
-    fn dont(&mut self) {
+    fn dont(&mut self) -> bool {
         if self.conditionals_enabled {
             self.instructions_enabled = false;
         }
+        self.conditionals_enabled
     }
 
-    fn add(&mut self, left: i32, right: i32) {
+    fn add(&mut self, left: i32, right: i32) -> bool {
         if self.instructions_enabled {
             self.total += left * right;
         }
+        self.instructions_enabled
     }
 }
 
-fn run_instructions(program: &str, iterpreter: &mut Interpreter) {
-    let re = Regex::new(r"mul\(([0-9]{1,3}),([0-9]{1,3})\)|(do\(\))|(don't\(\))").unwrap();
-    for elem in re.captures_iter(program) {
-        match (elem.get(1), elem.get(2), elem.get(3), elem.get(4)) {
-            (Some(l), Some(r), _, _) => {
-                let left = l.as_str().parse::<i32>().unwrap();
-                let right = r.as_str().parse::<i32>().unwrap();
-                iterpreter.add(left, right);
-            }
-            (_, _, Some(_), _) => iterpreter.do_(),
-            (_, _, _, Some(_)) => iterpreter.dont(),
-            _ => unreachable!(),
+fn run_instructions(program: &str, instructions: &InstructionSet, iterpreter: &mut Interpreter) {
+    for token in Tokenizer::new(program, instructions) {
+        let instruction = &instructions.0[token.instruction];
+        if (instruction.handler)(iterpreter, &token.args) {
+            iterpreter.trace.push(Executed {
+                offset: token.offset,
+                name: instruction.name.clone(),
+                args: token.args,
+            });
         }
     }
 }
 
 fn main() {
     let program = include_str!("../input");
+    let instructions = InstructionSet::standard();
 
     let mut part_1 = Interpreter::with_conditionals_disabled();
-    run_instructions(program, &mut part_1);
+    run_instructions(program, &instructions, &mut part_1);
     println!("Part 1: {}", part_1.total);
 
     let mut part_2 = Interpreter::with_conditionals_enabled();
-    run_instructions(program, &mut part_2);
-    println!("Part 1: {}", part_2.total);
+    run_instructions(program, &instructions, &mut part_2);
+    println!("Part 2: {}", part_2.total);
+
+    if std::env::args().any(|arg| arg == "--audit") {
+        let contributing = part_2.trace.iter().filter(|e| e.name == "mul");
+        println!(
+            "\n{} mul instructions contributed to Part 2:",
+            contributing.clone().count()
+        );
+        for executed in contributing {
+            println!("{executed}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE_1: &str =
+        "xmul(2,4)%&mul[3,7]!@^do_not_mul(5,5)+mul(32,64]then(mul(11,8)mul(8,5))";
+    const EXAMPLE_2: &str =
+        "xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))";
+
+    fn offsets(program: &str, instructions: &InstructionSet) -> Vec<(usize, String)> {
+        Tokenizer::new(program, instructions)
+            .map(|token| (token.offset, instructions.0[token.instruction].name.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn examples() {
+        let instructions = InstructionSet::standard();
+
+        let mut part_1 = Interpreter::with_conditionals_disabled();
+        run_instructions(EXAMPLE_1, &instructions, &mut part_1);
+        assert_eq!(part_1.total, 161);
+
+        let mut part_2 = Interpreter::with_conditionals_enabled();
+        run_instructions(EXAMPLE_2, &instructions, &mut part_2);
+        assert_eq!(part_2.total, 48);
+    }
+
+    #[test]
+    fn records_byte_offsets() {
+        let instructions = InstructionSet::standard();
+        let tokens = |program| {
+            offsets(program, &instructions)
+                .into_iter()
+                .map(|(offset, name)| format!("{offset} {name}"))
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(tokens(EXAMPLE_1), ["1 mul", "29 mul", "53 mul", "62 mul"]);
+        assert_eq!(
+            tokens(EXAMPLE_2),
+            ["1 mul", "20 don't", "28 mul", "48 mul", "59 do", "64 mul"]
+        );
+
+        // Only instructions that had an effect end up in the trace.
+        let mut interpreter = Interpreter::with_conditionals_enabled();
+        run_instructions(EXAMPLE_2, &instructions, &mut interpreter);
+        let trace = interpreter
+            .trace
+            .iter()
+            .map(Executed::to_string)
+            .collect::<Vec<String>>();
+        assert_eq!(
+            trace,
+            [
+                "     1: mul(2,4)",
+                "    20: don't()",
+                "    59: do()",
+                "    64: mul(8,5)"
+            ]
+        );
+    }
+
+    #[test]
+    fn user_defined_instruction() {
+        let mut instructions = InstructionSet::standard();
+        instructions.define("sq", 1, |interpreter, args| {
+            interpreter.add(args[0], args[0])
+        });
+
+        let program = "sq(3)mul(2,2)sq(1234)sq(4,4)don't()sq(5)";
+        let mut interpreter = Interpreter::with_conditionals_enabled();
+        run_instructions(program, &instructions, &mut interpreter);
+        assert_eq!(interpreter.total, 9 + 4);
+        assert_eq!(
+            offsets(program, &instructions)
+                .into_iter()
+                .map(|(offset, _)| offset)
+                .collect::<Vec<usize>>(),
+            [0, 5, 28, 35]
+        );
+    }
 }
@@ -1,69 +1,235 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 
-type Map = HashMap<(i32, i32), char>;
+// Up, right, down, left
+const HEADINGS: [(i32, i32); 4] = [(-1, 0), (0, 1), (1, 0), (0, -1)];
 
-fn build_map(data: &str) -> (Map, Option<(i32, i32)>) {
-    let mut map = HashMap::new();
+type Stops = Vec<Vec<Option<(i32, i32)>>>;
+
+struct Lab {
+    height: i32,
+    width: i32,
+    obstacles: Vec<Vec<bool>>,
+    // stops[heading][row][col] is where the guard ends up walking from (row, col)
+    // until the next obstacle, `None` if they walk off the map instead.
+    stops: [Stops; 4],
+}
+
+struct Patrol {
+    visited: Vec<(i32, i32)>,
+    loop_obstructions: Vec<(i32, i32)>,
+}
+
+fn build_map(data: &str) -> (Lab, Option<(i32, i32)>) {
     let mut guard = None;
+    let mut obstacles = Vec::new();
     for (row, line) in data.lines().enumerate() {
+        let mut cells = Vec::new();
         for (col, chr) in line.chars().enumerate() {
             if chr == '^' {
                 guard = Some((row as i32, col as i32));
-                map.insert((row as i32, col as i32), '.');
-                continue;
             }
-            map.insert((row as i32, col as i32), chr);
+            cells.push(chr == '#');
         }
+        obstacles.push(cells);
     }
-    (map, guard)
+    (Lab::new(obstacles), guard)
 }
 
-fn follow_guard(mut guard: (i32, i32), map: &mut Map, place_barriers: bool) -> bool {
-    let headings = [(-1, 0), (0, 1), (1, 0), (0, -1)];
-    let starting = guard.clone();
-    let mut seen = HashSet::new();
+impl Lab {
+    fn new(obstacles: Vec<Vec<bool>>) -> Self {
+        let height = obstacles.len() as i32;
+        let width = obstacles[0].len() as i32;
+        let mut lab = Self {
+            height,
+            width,
+            obstacles,
+            stops: Default::default(),
+        };
 
-    let mut headptr = 0;
-    let mut heading = headings[headptr];
-
-    while let Some(cand) = map.get(&(guard.0 + heading.0, guard.1 + heading.1)) {
-        if seen.contains(&((guard.0 + heading.0, guard.1 + heading.1), heading)) {
-            return true;
+        for (heading, offset) in HEADINGS.iter().enumerate() {
+            let mut stops = vec![vec![None; width as usize]; height as usize];
+            // Fill cells closest to the obstacle first, so each can reuse its neighbor.
+            let mut rows = (0..height).collect::<Vec<i32>>();
+            let mut cols = (0..width).collect::<Vec<i32>>();
+            if offset.0 > 0 {
+                rows.reverse();
+            }
+            if offset.1 > 0 {
+                cols.reverse();
+            }
+            for &row in &rows {
+                for &col in &cols {
+                    let next = (row + offset.0, col + offset.1);
+                    stops[row as usize][col as usize] = if !lab.on_map(next) {
+                        None
+                    } else if lab.is_obstacle(next) {
+                        Some((row, col))
+                    } else {
+                        stops[next.0 as usize][next.1 as usize]
+                    };
+                }
+            }
+            lab.stops[heading] = stops;
         }
+        lab
+    }
 
-        if cand == &'.' {
-            guard.0 += heading.0;
-            guard.1 += heading.1;
-            seen.insert((guard, heading));
+    fn on_map(&self, pos: (i32, i32)) -> bool {
+        pos.0 >= 0 && pos.1 >= 0 && pos.0 < self.height && pos.1 < self.width
+    }
+
+    fn is_obstacle(&self, pos: (i32, i32)) -> bool {
+        self.obstacles[pos.0 as usize][pos.1 as usize]
+    }
+
+    /// Walks straight until the next obstacle, taking an extra one into account.
+    fn jump(&self, pos: (i32, i32), heading: usize, extra: (i32, i32)) -> Option<(i32, i32)> {
+        let stop = self.stops[heading][pos.0 as usize][pos.1 as usize];
+        let (dr, dc) = HEADINGS[heading];
+        let ahead = |p: (i32, i32)| (p.0 - pos.0) * dr + (p.1 - pos.1) * dc;
+
+        let in_line = if dr == 0 {
+            extra.0 == pos.0
         } else {
-            headptr = (headptr + 1).rem_euclid(4);
-            heading = headings[headptr];
+            extra.1 == pos.1
+        };
+        let hits_extra =
+            in_line && ahead(extra) > 0 && stop.is_none_or(|s| ahead(extra) <= ahead(s) + 1);
+
+        if hits_extra {
+            Some((extra.0 - dr, extra.1 - dc))
+        } else {
+            stop
+        }
+    }
+
+    /// Loop detection only has to look at the turns, keyed on (position, heading).
+    fn loops(&self, mut pos: (i32, i32), mut heading: usize, extra: (i32, i32)) -> bool {
+        let mut seen = HashSet::new();
+        while let Some(stop) = self.jump(pos, heading, extra) {
+            if !seen.insert((stop, heading)) {
+                return true;
+            }
+            pos = stop;
+            heading = (heading + 1) % 4;
         }
+        false
     }
 
-    if place_barriers {
-        let visited = seen
-            .iter()
-            .map(|elem| elem.0)
-            .collect::<HashSet<(i32, i32)>>();
+    fn follow_guard(&self, start: (i32, i32)) -> Patrol {
+        let mut visited = vec![start];
+        let mut tested = HashSet::from([start]);
+        let mut loop_obstructions = Vec::new();
 
-        println!("Part 1: {}", visited.len());
+        let (mut guard, mut heading) = (start, 0);
+        loop {
+            let next = (guard.0 + HEADINGS[heading].0, guard.1 + HEADINGS[heading].1);
+            if !self.on_map(next) {
+                break;
+            }
+
+            if self.is_obstacle(next) {
+                heading = (heading + 1) % 4;
+                continue;
+            }
 
-        let mut looping = 0;
-        for barrier in visited {
-            map.insert(barrier, '#');
-            if follow_guard(starting, map, false) {
-                looping += 1
+            // An obstruction only matters where the guard would walk into it for the
+            // first time, so resume the patrol from right in front of it.
+            if tested.insert(next) {
+                visited.push(next);
+                if self.loops(guard, heading, next) {
+                    loop_obstructions.push(next);
+                }
             }
-            map.insert(barrier, '.');
+            guard = next;
+        }
+
+        Patrol {
+            visited,
+            loop_obstructions,
         }
-        println!("Part 2: {looping}");
     }
-    false
 }
 
 fn main() {
     let data = include_str!("../input");
-    let (mut map, guard) = build_map(data);
-    follow_guard(guard.unwrap(), &mut map, true);
+    let (lab, guard) = build_map(data);
+
+    let patrol = lab.follow_guard(guard.unwrap());
+    println!("Part 1: {}", patrol.visited.len());
+    println!("Part 2: {}", patrol.loop_obstructions.len());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+....#.....
+.........#
+..........
+..#.......
+.......#..
+..........
+.#..^.....
+........#.
+#.........
+......#...";
+
+    /// Walks one cell at a time, the way the puzzle describes it.
+    fn loops_naively(lab: &Lab, start: (i32, i32), extra: (i32, i32)) -> bool {
+        let (mut guard, mut heading) = (start, 0);
+        let mut seen = HashSet::new();
+        while seen.insert((guard, heading)) {
+            let next = (guard.0 + HEADINGS[heading].0, guard.1 + HEADINGS[heading].1);
+            if !lab.on_map(next) {
+                return false;
+            }
+            if lab.is_obstacle(next) || next == extra {
+                heading = (heading + 1) % 4;
+            } else {
+                guard = next;
+            }
+        }
+        true
+    }
+
+    #[test]
+    fn example() {
+        let (lab, guard) = build_map(EXAMPLE);
+        let patrol = lab.follow_guard(guard.unwrap());
+        assert_eq!(patrol.visited.len(), 41);
+
+        let mut obstructions = patrol.loop_obstructions.clone();
+        obstructions.sort_unstable();
+        assert_eq!(
+            obstructions,
+            vec![(6, 3), (7, 6), (7, 7), (8, 1), (8, 3), (9, 7)]
+        );
+
+        for pos in &patrol.visited[1..] {
+            assert_eq!(
+                patrol.loop_obstructions.contains(pos),
+                loops_naively(&lab, guard.unwrap(), *pos),
+                "obstruction at {pos:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn jumps_to_the_nearest_obstacle() {
+        let (lab, _) = build_map(EXAMPLE);
+        let nowhere = (-5, -5);
+        // Up from the guard, until the obstacle at (0, 4).
+        assert_eq!(lab.jump((6, 4), 0, nowhere), Some((1, 4)));
+        assert_eq!(lab.jump((6, 4), 0, (4, 4)), Some((5, 4)));
+        // An extra obstacle behind the guard changes nothing, nor does one on top of
+        // the real obstacle.
+        assert_eq!(lab.jump((6, 4), 0, (7, 4)), Some((1, 4)));
+        assert_eq!(lab.jump((6, 4), 0, (0, 4)), Some((1, 4)));
+        // Walking off the map, unless the extra obstacle is in the way.
+        assert_eq!(lab.jump((6, 4), 2, nowhere), None);
+        assert_eq!(lab.jump((6, 4), 2, (9, 4)), Some((8, 4)));
+        assert_eq!(lab.jump((6, 4), 1, (6, 5)), Some((6, 4)));
+    }
 }
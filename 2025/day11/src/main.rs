@@ -1,70 +1,197 @@
+use num::{BigUint, One, Zero};
 use std::collections::HashMap;
+use std::fmt;
 
-fn build_graph(data: &str) -> HashMap<&str, Vec<&str>> {
-    let mut graph = HashMap::new();
+#[derive(Debug)]
+struct CycleError(Vec<String>);
 
-    for line in data.lines() {
-        let mut parts = line.split(':');
-        let src = parts.next().unwrap();
-
-        let destinations = parts
-            .next()
-            .unwrap()
-            .split_whitespace()
-            .collect::<Vec<&str>>();
-
-        graph.insert(src, destinations);
+impl fmt::Display for CycleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Graph has a cycle: {}", self.0.join(" -> "))
     }
-
-    graph
 }
 
-fn traverse(graph: &HashMap<&str, Vec<&str>>, src: &str, dest: &str) -> usize {
-    let mut m = HashMap::new();
-    step(src, dest, graph, &mut m)
+struct Graph<'a> {
+    edges: HashMap<&'a str, Vec<&'a str>>,
 }
 
-fn step<'a>(
-    current: &'a str,
-    dest: &str,
-    graph: &'a HashMap<&str, Vec<&str>>,
-    m: &mut HashMap<&'a str, usize>,
-) -> usize {
-    if current == dest {
-        return 1;
+impl<'a> Graph<'a> {
+    fn build(data: &'a str) -> Self {
+        let mut edges = HashMap::new();
+
+        for line in data.lines() {
+            let mut parts = line.split(':');
+            let src = parts.next().unwrap();
+
+            let destinations = parts
+                .next()
+                .unwrap()
+                .split_whitespace()
+                .collect::<Vec<&str>>();
+
+            edges.insert(src, destinations);
+        }
+
+        Self { edges }
     }
 
-    if let Some(known) = m.get(current) {
-        return *known;
+    fn neighbors(&self, node: &str) -> &[&'a str] {
+        self.edges.get(node).map_or(&[], |n| n.as_slice())
     }
 
-    let mut paths = 0;
-    if let Some(destinations) = graph.get(current) {
-        for next in destinations {
-            paths += step(next, dest, graph, m);
+    /// Nodes reachable from `src`, in topological order.
+    fn topological_order(&self, src: &'a str) -> Result<Vec<&'a str>, CycleError> {
+        #[derive(PartialEq)]
+        enum State {
+            InProgress,
+            Done,
+        }
+
+        let mut state = HashMap::new();
+        let mut order = Vec::new();
+        // Iterative DFS, the stack doubles as the current path for cycle reporting.
+        let mut stack = vec![(src, 0)];
+        state.insert(src, State::InProgress);
+
+        while let Some((node, next)) = stack.last_mut() {
+            let node = *node;
+            if let Some(neighbor) = self.neighbors(node).get(*next).copied() {
+                *next += 1;
+                match state.get(neighbor) {
+                    Some(State::Done) => {}
+                    Some(State::InProgress) => {
+                        let start = stack.iter().position(|(n, _)| *n == neighbor).unwrap();
+                        let mut cycle = stack[start..]
+                            .iter()
+                            .map(|(n, _)| n.to_string())
+                            .collect::<Vec<String>>();
+                        cycle.push(neighbor.to_string());
+                        return Err(CycleError(cycle));
+                    }
+                    None => {
+                        state.insert(neighbor, State::InProgress);
+                        stack.push((neighbor, 0));
+                    }
+                }
+            } else {
+                state.insert(node, State::Done);
+                order.push(node);
+                stack.pop();
+            }
         }
+
+        order.reverse();
+        Ok(order)
     }
 
-    m.insert(current, paths);
+    /// Counts `src` to `dest` paths that visit every waypoint, in any order.
+    fn count_paths(
+        &self,
+        src: &'a str,
+        dest: &str,
+        waypoints: &[&str],
+    ) -> Result<BigUint, CycleError> {
+        let order = self.topological_order(src)?;
+        let positions = order
+            .iter()
+            .enumerate()
+            .map(|(idx, node)| (*node, idx))
+            .collect::<HashMap<&str, usize>>();
+
+        // In a DAG, waypoints can only ever be visited in topological order, and
+        // all of them before `dest`.
+        let Some(&end) = positions.get(dest) else {
+            return Ok(BigUint::zero());
+        };
+        let mut legs = Vec::with_capacity(waypoints.len() + 2);
+        for node in waypoints {
+            match positions.get(node) {
+                Some(pos) if *pos <= end => legs.push(*pos),
+                _ => return Ok(BigUint::zero()),
+            }
+        }
+        legs.sort_unstable();
+        legs.insert(0, 0);
+        legs.push(end);
+
+        let mut total = BigUint::one();
+        for leg in legs.windows(2) {
+            total *= self.count_leg(&order, &positions, leg[0], leg[1]);
+        }
+        Ok(total)
+    }
 
-    paths
+    fn count_leg(
+        &self,
+        order: &[&'a str],
+        positions: &HashMap<&str, usize>,
+        from: usize,
+        to: usize,
+    ) -> BigUint {
+        let mut paths = vec![BigUint::zero(); to - from + 1];
+        paths[0] = BigUint::one();
+        for idx in from..to {
+            if paths[idx - from].is_zero() {
+                continue;
+            }
+
+            let current = paths[idx - from].clone();
+            for next in self.neighbors(order[idx]) {
+                let pos = positions[next];
+                if pos <= to {
+                    paths[pos - from] += &current;
+                }
+            }
+        }
+        paths.pop().unwrap()
+    }
 }
 
 fn main() {
     let data = include_str!("input");
 
-    let graph = build_graph(data);
-    let part_1 = traverse(&graph, "you", "out");
+    let graph = Graph::build(data);
+    match graph.count_paths("you", "out", &[]) {
+        Ok(part_1) => println!("Part 1: {part_1}"),
+        Err(e) => println!("Part 1: {e}"),
+    }
+
+    match graph.count_paths("svr", "out", &["fft", "dac"]) {
+        Ok(part_2) => println!("Part 2: {part_2}"),
+        Err(e) => println!("Part 2: {e}"),
+    }
+}
 
-    println!("Part 2: {part_1}");
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let fft_to_dac = traverse(&graph, "svr", "fft")
-        * traverse(&graph, "fft", "dac")
-        * traverse(&graph, "dac", "out");
+    #[test]
+    fn waypoint_after_dest() {
+        let graph = Graph::build("svr: out\nout: fft\nfft: dac\n");
+        let paths = graph.count_paths("svr", "out", &["fft", "dac"]).unwrap();
+        assert!(paths.is_zero());
+    }
 
-    let dac_to_fft = traverse(&graph, "svr", "dac")
-        * traverse(&graph, "dac", "fft")
-        * traverse(&graph, "fft", "out");
+    #[test]
+    fn waypoints_in_any_order() {
+        let graph = Graph::build("svr: dac\ndac: fft\nfft: out\n");
+        let paths = graph.count_paths("svr", "out", &["fft", "dac"]).unwrap();
+        assert_eq!(paths, BigUint::one());
+    }
 
-    println!("Part 2: {}", fft_to_dac + dac_to_fft);
+    #[test]
+    fn reports_cycles() {
+        let graph = Graph::build("you: a\na: b\nb: a out\n");
+        let err = graph.count_paths("you", "out", &[]).unwrap_err();
+        assert_eq!(err.0, vec!["a", "b", "a"]);
+        assert_eq!(err.to_string(), "Graph has a cycle: a -> b -> a");
+
+        // A cycle that can't be reached from the source doesn't matter.
+        let graph = Graph::build("you: out\na: b\nb: a\n");
+        assert_eq!(
+            graph.count_paths("you", "out", &[]).unwrap(),
+            BigUint::one()
+        );
+    }
 }
@@ -0,0 +1,96 @@
+use crate::polygon::Polygon;
+use std::collections::{HashMap, VecDeque};
+
+/// Polygon tiles on a coordinate-compressed grid. Odd indices map to the exact
+/// vertex coordinates, even indices to the open gap between two neighboring ones,
+/// so every compressed cell is either fully inside or fully outside.
+pub struct CompressedGrid {
+    xs: HashMap<i64, usize>,
+    ys: HashMap<i64, usize>,
+    // 2D prefix sum of compressed cells that lie outside and hold at least one tile.
+    outside: Vec<Vec<u32>>,
+}
+
+impl CompressedGrid {
+    pub fn new(polygon: &Polygon) -> Self {
+        let axis = |coord: fn(&(i64, i64)) -> i64| {
+            let mut values = polygon.vertices().iter().map(coord).collect::<Vec<i64>>();
+            values.sort_unstable();
+            values.dedup();
+            values
+        };
+        let xs = axis(|v| v.0);
+        let ys = axis(|v| v.1);
+
+        // One cell of padding around the grid, so the flood fill can go all around.
+        let width = xs.len() * 2 + 1;
+        let height = ys.len() * 2 + 1;
+        let x_index = |x: i64| xs.binary_search(&x).unwrap() * 2 + 1;
+        let y_index = |y: i64| ys.binary_search(&y).unwrap() * 2 + 1;
+
+        let mut boundary = vec![vec![false; width]; height];
+        for (a, b) in polygon.edges() {
+            let (x1, x2) = (x_index(a.0.min(b.0)), x_index(a.0.max(b.0)));
+            let (y1, y2) = (y_index(a.1.min(b.1)), y_index(a.1.max(b.1)));
+            for row in boundary.iter_mut().take(y2 + 1).skip(y1) {
+                for cell in row.iter_mut().take(x2 + 1).skip(x1) {
+                    *cell = true;
+                }
+            }
+        }
+
+        let mut outside_cells = vec![vec![false; width]; height];
+        let mut queue = VecDeque::from([(0usize, 0usize)]);
+        outside_cells[0][0] = true;
+        while let Some((row, col)) = queue.pop_front() {
+            let neighbors = [
+                (row.wrapping_sub(1), col),
+                (row + 1, col),
+                (row, col.wrapping_sub(1)),
+                (row, col + 1),
+            ];
+            for (r, c) in neighbors {
+                if r < height && c < width && !boundary[r][c] && !outside_cells[r][c] {
+                    outside_cells[r][c] = true;
+                    queue.push_back((r, c));
+                }
+            }
+        }
+
+        // Number of tiles a compressed cell stands for along a single axis.
+        let span = |values: &[i64], idx: usize| -> i64 {
+            match idx {
+                0 => 0,
+                idx if idx == values.len() * 2 => 0,
+                idx if idx % 2 == 1 => 1,
+                idx => values[idx / 2] - values[idx / 2 - 1] - 1,
+            }
+        };
+
+        let mut outside = vec![vec![0; width + 1]; height + 1];
+        for row in 0..height {
+            for col in 0..width {
+                let tiles = span(&ys, row) as i128 * span(&xs, col) as i128;
+                let bad = outside_cells[row][col] && tiles > 0;
+                outside[row + 1][col + 1] = outside[row][col + 1] + outside[row + 1][col]
+                    - outside[row][col]
+                    + u32::from(bad);
+            }
+        }
+
+        Self {
+            xs: xs.iter().map(|x| (*x, x_index(*x))).collect(),
+            ys: ys.iter().map(|y| (*y, y_index(*y))).collect(),
+            outside,
+        }
+    }
+
+    /// Whether the rectangle spanned by two vertex-aligned corners only covers
+    /// polygon tiles. Both corners have to share coordinates with some vertex.
+    pub fn contains_rect(&self, a: (i64, i64), b: (i64, i64)) -> bool {
+        let (x1, x2) = (self.xs[&a.0.min(b.0)], self.xs[&a.0.max(b.0)]);
+        let (y1, y2) = (self.ys[&a.1.min(b.1)], self.ys[&a.1.max(b.1)]);
+        let o = &self.outside;
+        o[y2 + 1][x2 + 1] + o[y1][x1] - o[y1][x2 + 1] - o[y2 + 1][x1] == 0
+    }
+}
@@ -1,5 +1,9 @@
-use geo::Within;
-use geo::{LineString, Polygon};
+mod grid;
+#[path = "../../../common/polygon.rs"]
+mod polygon;
+
+use grid::CompressedGrid;
+use polygon::Polygon;
 
 #[derive(Debug)]
 struct Tile {
@@ -33,35 +37,22 @@ fn find_biggest_rectangle(tiles: &[Tile]) -> isize {
 }
 
 fn find_biggest_contained_rectangle(tiles: &[Tile]) -> isize {
-    let line_string: LineString<f32> = tiles
-        .iter()
-        .map(|t| (t.x as f32, t.y as f32))
-        .collect::<Vec<(f32, f32)>>()
-        .into();
+    let polygon = Polygon::new(tiles.iter().map(|t| (t.x as i64, t.y as i64)).collect());
+    let grid = CompressedGrid::new(&polygon);
 
-    let poly = Polygon::new(line_string, vec![]);
     let mut max_area = 0;
-
     for (covered, left_tile) in tiles.iter().enumerate() {
         for right_tile in tiles.iter().skip(covered + 1) {
-            let small_poly = Polygon::new(
-                vec![
-                    (left_tile.x as f32, left_tile.y as f32),
-                    (right_tile.x as f32, left_tile.y as f32),
-                    (right_tile.x as f32, right_tile.y as f32),
-                    (left_tile.x as f32, right_tile.y as f32),
-                ]
-                .into(),
-                vec![],
-            );
-
-            if small_poly.is_within(&poly) {
-                let area = ((right_tile.x - left_tile.x).abs() + 1)
-                    * ((right_tile.y - left_tile.y).abs() + 1);
-
-                if area > max_area {
-                    max_area = area;
-                }
+            let area =
+                ((right_tile.x - left_tile.x).abs() + 1) * ((right_tile.y - left_tile.y).abs() + 1);
+
+            if area > max_area
+                && grid.contains_rect(
+                    (left_tile.x as i64, left_tile.y as i64),
+                    (right_tile.x as i64, right_tile.y as i64),
+                )
+            {
+                max_area = area;
             }
         }
     }
@@ -80,3 +71,25 @@ fn main() {
     let part_2 = find_biggest_contained_rectangle(&tiles);
     println!("Part 2: {part_2}");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "7,1\n11,1\n11,7\n9,7\n9,5\n2,5\n2,3\n7,3\n";
+
+    #[test]
+    fn example() {
+        let tiles = EXAMPLE.lines().map(Tile::from).collect::<Vec<Tile>>();
+        assert_eq!(find_biggest_rectangle(&tiles), 50);
+        assert_eq!(find_biggest_contained_rectangle(&tiles), 24);
+    }
+
+    #[test]
+    fn picks_theorem() {
+        let tiles = EXAMPLE.lines().map(Tile::from).collect::<Vec<Tile>>();
+        let polygon = Polygon::new(tiles.iter().map(|t| (t.x as i64, t.y as i64)).collect());
+        assert_eq!(polygon.boundary_points(), 30);
+        assert_eq!(polygon.lattice_points(), 46);
+    }
+}
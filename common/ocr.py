@@ -0,0 +1,73 @@
+"""Reads the block letters some puzzles draw, the Python twin of ocr.rs.
+
+Both read their fonts from ocr_fonts.txt, the 4x6 one and the 6x10 one.
+"""
+from pathlib import Path
+from typing import Dict, List, Sequence, Tuple
+
+FONTS_PATH = Path(__file__).with_name("ocr_fonts.txt")
+
+
+class OcrError(ValueError):
+    pass
+
+
+def load_font(height: int) -> Dict[Tuple[str, ...], str]:
+
+    font = {}
+    for glyph in FONTS_PATH.read_text().split("\n\n"):
+        letter, *rows = glyph.strip().split("\n")
+        if len(rows) == height:
+            font[crop(rows)] = letter
+    return font
+
+
+def crop(rows: Sequence[str]) -> Tuple[str, ...]:
+
+    used = [col for col in range(len(rows[0])) if any(row[col] == "#" for row in rows)]
+    return tuple(row[used[0] : used[-1] + 1] for row in rows)
+
+
+def recognise(pixels: Sequence[Sequence[bool]]) -> str:
+    """Blank rows around the text are ignored, letters are told apart by the
+    blank columns between them. Raises an OcrError listing the (index, column)
+    of every glyph that isn't in the font."""
+
+    lit_rows = [idx for idx, row in enumerate(pixels) if any(row)]
+    if not lit_rows:
+        return ""
+    rows = pixels[lit_rows[0] : lit_rows[-1] + 1]
+
+    font = load_font(len(rows))
+    if not font:
+        raise OcrError(f"No font is {len(rows)} pixels high, only 6 and 10")
+
+    width = max(len(row) for row in rows)
+    lit = lambda r, c: c < len(rows[r]) and bool(rows[r][c])
+    blank = [not any(lit(r, c) for r in range(len(rows))) for c in range(width)]
+
+    text: List[str] = []
+    unrecognised: List[Tuple[int, int]] = []
+    col = 0
+    while col < width:
+        if blank[col]:
+            col += 1
+            continue
+
+        left = col
+        while col < width and not blank[col]:
+            col += 1
+        glyph = tuple(
+            "".join("#" if lit(r, c) else "." for c in range(left, col))
+            for r in range(len(rows))
+        )
+
+        if glyph in font:
+            text.append(font[glyph])
+        else:
+            unrecognised.append((len(text) + len(unrecognised), left))
+
+    if unrecognised:
+        positions = ", ".join(f"#{idx} at column {col}" for idx, col in unrecognised)
+        raise OcrError(f"Unrecognised glyphs: {positions}")
+    return "".join(text)
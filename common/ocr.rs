@@ -0,0 +1,185 @@
+//! Reads the block letters some puzzles draw, shared between days by `#[path]`.
+//! Knows the 4x6 font (CRTs, folded paper) and the 6x10 one (stars aligning).
+
+use std::fmt;
+
+// Every glyph is its letter on a line of its own, followed by its rows of pixels
+// with `#` for lit ones. Shared with the Python solutions.
+const FONTS: &str = include_str!("ocr_fonts.txt");
+
+/// Glyphs of the font that is `height` pixels high.
+fn font(height: usize) -> Vec<(char, Vec<&'static str>)> {
+    FONTS
+        .split("\n\n")
+        .map(|glyph| {
+            let mut lines = glyph.lines();
+            let letter = lines.next().unwrap().chars().next().unwrap();
+            (letter, lines.collect::<Vec<&str>>())
+        })
+        .filter(|(_, rows)| rows.len() == height)
+        .collect()
+}
+
+#[derive(Debug)]
+pub enum OcrError {
+    /// Lit pixels span a number of rows neither font has.
+    UnsupportedHeight(usize),
+    /// Glyphs that aren't in the font, as (index, leftmost column).
+    Unrecognised(Vec<(usize, usize)>),
+}
+
+impl fmt::Display for OcrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedHeight(height) => {
+                write!(f, "No font is {height} pixels high, only 6 and 10")
+            }
+            Self::Unrecognised(glyphs) => {
+                let positions = glyphs
+                    .iter()
+                    .map(|(idx, col)| format!("#{idx} at column {col}"))
+                    .collect::<Vec<String>>();
+                write!(f, "Unrecognised glyphs: {}", positions.join(", "))
+            }
+        }
+    }
+}
+
+/// Reads the letters off a grid of lit pixels. Blank rows around the text are
+/// ignored, and letters are told apart by the blank columns between them.
+pub fn recognise(pixels: &[Vec<bool>]) -> Result<String, OcrError> {
+    let lit_rows = (0..pixels.len())
+        .filter(|row| pixels[*row].iter().any(|p| *p))
+        .collect::<Vec<usize>>();
+    let (Some(top), Some(bottom)) = (lit_rows.first(), lit_rows.last()) else {
+        return Ok(String::new());
+    };
+    let rows = &pixels[*top..=*bottom];
+
+    let font = font(rows.len());
+    if font.is_empty() {
+        return Err(OcrError::UnsupportedHeight(rows.len()));
+    }
+
+    let width = rows.iter().map(Vec::len).max().unwrap();
+    let lit = |row: usize, col: usize| rows[row].get(col).copied().unwrap_or(false);
+    let blank_column = |col: usize| (0..rows.len()).all(|row| !lit(row, col));
+
+    let mut text = String::new();
+    let mut unrecognised = Vec::new();
+    let mut col = 0;
+    while col < width {
+        if blank_column(col) {
+            col += 1;
+            continue;
+        }
+
+        let left = col;
+        while col < width && !blank_column(col) {
+            col += 1;
+        }
+        let glyph = (0..rows.len())
+            .map(|row| {
+                (left..col)
+                    .map(|c| if lit(row, c) { '#' } else { '.' })
+                    .collect::<String>()
+            })
+            .collect::<Vec<String>>();
+
+        match font.iter().find(|(_, pattern)| same_glyph(pattern, &glyph)) {
+            Some((letter, _)) => text.push(*letter),
+            None => unrecognised.push((text.len() + unrecognised.len(), left)),
+        }
+    }
+
+    if unrecognised.is_empty() {
+        Ok(text)
+    } else {
+        Err(OcrError::Unrecognised(unrecognised))
+    }
+}
+
+/// Compares a font glyph to a cropped one, ignoring the font's blank margins.
+fn same_glyph(pattern: &[&str], glyph: &[String]) -> bool {
+    let width = pattern[0].len();
+    let used = (0..width)
+        .filter(|col| pattern.iter().any(|row| row.as_bytes()[*col] == b'#'))
+        .collect::<Vec<usize>>();
+    let (left, right) = (used[0], used[used.len() - 1]);
+
+    pattern
+        .iter()
+        .zip(glyph)
+        .all(|(row, cropped)| row[left..=right] == *cropped)
+}
+
+#[cfg(test)]
+mod ocr_tests {
+    use super::*;
+
+    /// Draws text in a font, with a blank column between letters.
+    fn draw(text: &str, height: usize) -> Vec<Vec<bool>> {
+        let font = font(height);
+        let mut pixels = vec![Vec::new(); height];
+        for letter in text.chars() {
+            let (_, glyph) = font.iter().find(|(l, _)| *l == letter).unwrap();
+            for (row, line) in pixels.iter_mut().zip(glyph) {
+                row.extend(line.chars().map(|c| c == '#'));
+                row.push(false);
+            }
+        }
+        pixels
+    }
+
+    #[test]
+    fn reads_both_fonts() {
+        assert_eq!(
+            recognise(&draw("ABCEFGHIJKLOPRSUYZ", 6)).unwrap(),
+            "ABCEFGHIJKLOPRSUYZ"
+        );
+        assert_eq!(
+            recognise(&draw("ABCEFGHJKLNPRXZ", 10)).unwrap(),
+            "ABCEFGHJKLNPRXZ"
+        );
+    }
+
+    #[test]
+    fn reads_fixture() {
+        // A real CRT screen, so a glyph drawn wrong in the font file shows up.
+        let pixels = include_str!("ocr_fixture.txt")
+            .lines()
+            .map(|line| line.chars().map(|c| c == '#').collect())
+            .collect::<Vec<Vec<bool>>>();
+        assert_eq!(recognise(&pixels).unwrap(), "ERCREPCJ");
+    }
+
+    #[test]
+    fn ignores_blank_margins() {
+        let mut pixels = draw("HX", 10);
+        pixels.insert(0, vec![false; 3]);
+        pixels.push(Vec::new());
+        assert_eq!(recognise(&pixels).unwrap(), "HX");
+    }
+
+    #[test]
+    fn reports_unknown_glyphs() {
+        let mut pixels = draw("OLO", 6);
+        // Turn the middle letter into something that isn't an L.
+        pixels[0][6] = true;
+        match recognise(&pixels) {
+            Err(OcrError::Unrecognised(glyphs)) => assert_eq!(glyphs, vec![(1, 5)]),
+            other => panic!("Expected an unrecognised glyph, got {other:?}"),
+        }
+        // A blank row inside the text still counts towards its height.
+        let mut gapped = draw("L", 6);
+        gapped[1].fill(false);
+        assert!(matches!(
+            recognise(&gapped),
+            Err(OcrError::Unrecognised(glyphs)) if glyphs == vec![(0, 0)]
+        ));
+        assert!(matches!(
+            recognise(&vec![vec![true]; 7]),
+            Err(OcrError::UnsupportedHeight(7))
+        ));
+    }
+}
@@ -0,0 +1,58 @@
+//! Closed rectilinear polygons on the integer lattice, shared between days by
+//! `#[path]`. Every vertex and every point on an edge is a lattice point (a tile).
+
+pub struct Polygon {
+    vertices: Vec<(i64, i64)>,
+}
+
+impl Polygon {
+    pub fn new(vertices: Vec<(i64, i64)>) -> Self {
+        for (idx, a) in vertices.iter().enumerate() {
+            let b = vertices[(idx + 1) % vertices.len()];
+            assert!(
+                a.0 == b.0 || a.1 == b.1,
+                "Edge {a:?} -> {b:?} is not axis-aligned"
+            );
+        }
+        Self { vertices }
+    }
+
+    pub fn vertices(&self) -> &[(i64, i64)] {
+        &self.vertices
+    }
+
+    pub fn edges(&self) -> impl Iterator<Item = ((i64, i64), (i64, i64))> + '_ {
+        self.vertices
+            .iter()
+            .enumerate()
+            .map(|(idx, a)| (*a, self.vertices[(idx + 1) % self.vertices.len()]))
+    }
+}
+
+// Counting tiles is what 2023/day18 needs, 2025/day9 only walks the outline.
+#[allow(dead_code)]
+impl Polygon {
+    /// Shoelace formula, doubled so that it stays an integer.
+    pub fn double_area(&self) -> i128 {
+        self.edges()
+            .map(|(a, b)| a.0 as i128 * b.1 as i128 - b.0 as i128 * a.1 as i128)
+            .sum::<i128>()
+            .abs()
+    }
+
+    pub fn boundary_points(&self) -> i128 {
+        self.edges()
+            .map(|(a, b)| (a.0.abs_diff(b.0) + a.1.abs_diff(b.1)) as i128)
+            .sum()
+    }
+
+    /// Pick's theorem: A = I + B/2 - 1.
+    pub fn interior_points(&self) -> i128 {
+        (self.double_area() - self.boundary_points() + 2) / 2
+    }
+
+    /// Every tile on or inside the polygon.
+    pub fn lattice_points(&self) -> i128 {
+        self.interior_points() + self.boundary_points()
+    }
+}
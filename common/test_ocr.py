@@ -0,0 +1,40 @@
+"""Checks ocr.py against the same fixture as the ocr.rs tests.
+
+Run with `python3 -m unittest test_ocr` from this directory.
+"""
+import unittest
+from pathlib import Path
+
+import ocr
+
+FIXTURE = Path(__file__).with_name("ocr_fixture.txt").read_text().splitlines()
+
+
+def pixels(rows):
+
+    return [[c == "#" for c in row] for row in rows]
+
+
+class RecogniseTest(unittest.TestCase):
+    def test_reads_fixture(self):
+
+        self.assertEqual(ocr.recognise(pixels(FIXTURE)), "ERCREPCJ")
+
+    def test_blank_row_inside_letters(self):
+
+        # An L missing its second row spans all 6 rows, so it is looked up in
+        # the small font and merely unrecognised.
+        rows = ["#...", "....", "#...", "#...", "#...", "####"]
+        with self.assertRaisesRegex(ocr.OcrError, "Unrecognised glyphs: #0"):
+            ocr.recognise(pixels([""] + rows + ["...."]))
+
+    def test_reports_unknown_glyphs(self):
+
+        rows = list(FIXTURE)
+        rows[0] = "#" * len(rows[0])
+        with self.assertRaises(ocr.OcrError):
+            ocr.recognise(pixels(rows))
+
+
+if __name__ == "__main__":
+    unittest.main()